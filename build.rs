@@ -6,6 +6,7 @@ use std::{env, fs, io::Write, path::Path, process::Command};
 fn main() {
     dump_git_version();
     build_wayland_glib_interface();
+    link_libinput();
 }
 
 /// Writes the current git hash to a file that is read by Way Cooler
@@ -58,3 +59,8 @@ fn build_wayland_glib_interface() {
     builder.file("src/wayland_glib_interface.c")
            .compile("wayland_glib_interface");
 }
+
+/// Link against libinput, which is used directly to configure input devices.
+fn link_libinput() {
+    pkg_config::probe_library("libinput").expect("Could not find libinput");
+}
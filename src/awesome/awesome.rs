@@ -1,43 +1,28 @@
 //! TODO Fill in
 
-use super::{signal, XCB_CONNECTION_HANDLE};
+use super::{signal, XCB_CONNECTION_HANDLE, XKB};
 use super::xproperty::{XProperty, XPropertyType, PROPERTIES};
 use awesome::lua::{load_config, rust_interop, LUA};
 use cairo::{self, ImageSurface, ImageSurfaceData};
+use compositor::{self, FocusPolicy, RepeatInfo, XkbConfig};
 use gdk_pixbuf::{Pixbuf, PixbufExt};
 use glib::translate::ToGlibPtr;
 use nix::{self, libc};
-use rlua::{self, AnyUserData, LightUserData, Lua, MetaMethod, Table, ToLua, UserData,
-           UserDataMethods, Value};
-use std::{mem, ptr};
+use rlua::{self, AnyUserData, LightUserData, Lua, MetaMethod, MultiValue, Table, ToLua, ToLuaMulti,
+           UserData, UserDataMethods, Value};
+use std::ptr;
 use std::default::Default;
-use std::ffi::{CStr, CString};
+use std::ffi::CString;
 use std::fmt::{self, Display, Formatter};
 use std::process::{Command, Stdio};
 use std::thread;
-use xcb::{xkb, Connection};
-use xcb::ffi::{self, xproto};
+use std::time::Duration;
+use xcb::ffi::xproto;
 
 // TODO FIXME
 // Often we are getting some raw pointers from the xcb replies
 // we need to free them because the memory management for them is manual.
 
-// TODO this isn't defined in the xcb crate, even though it should be.
-// A patch should be open adding this to its generation scheme
-extern "C" {
-    fn xcb_xkb_get_names_value_list_unpack(buffer: *mut libc::c_void,
-                                           nTypes: u8,
-                                           indicators: u32,
-                                           virtualMods: u16,
-                                           groupNames: u8,
-                                           nKeys: u8,
-                                           nKeyAliases: u8,
-                                           nRadioGroups: u8,
-                                           which: u32,
-                                           aux: *mut ffi::xkb::xcb_xkb_get_names_value_list_t)
-                                           -> libc::c_int;
-}
-
 #[derive(Clone, Debug)]
 pub struct AwesomeState {
     preferred_icon_size: u32
@@ -84,14 +69,24 @@ fn method_setup<'lua>(lua: &'lua Lua, awesome_table: &Table<'lua>) -> rlua::Resu
     awesome_table.set("disconnect_signal",
                        lua.create_function(signal::global_disconnect_signal)?)?;
     awesome_table.set("emit_signal",
-                       lua.create_function(signal::global_emit_signal)?)?;
+                       lua.create_function(|lua, args: (String, MultiValue)| {
+                                               signal::global_emit_signal(lua, args)
+                                           })?)?;
     awesome_table.set("xrdb_get_value", lua.create_function(xrdb_get_value)?)?;
     awesome_table.set("xkb_set_layout_group",
                        lua.create_function(xkb_set_layout_group)?)?;
     awesome_table.set("xkb_get_layout_group",
                        lua.create_function(xkb_get_layout_group)?)?;
+    awesome_table.set("set_xkb_layout", lua.create_function(set_xkb_layout)?)?;
+    awesome_table.set("set_keyboard_repeat",
+                       lua.create_function(set_keyboard_repeat)?)?;
+    awesome_table.set("set_idle_timeouts", lua.create_function(set_idle_timeouts)?)?;
+    awesome_table.set("unconstrain_pointer",
+                       lua.create_function(unconstrain_pointer)?)?;
     awesome_table.set("set_preferred_icon_size",
                        lua.create_function(set_preferred_icon_size)?)?;
+    awesome_table.set("set_focus_policy", lua.create_function(set_focus_policy)?)?;
+    awesome_table.set("set_cursor_theme", lua.create_function(set_cursor_theme)?)?;
     awesome_table.set("register_xproperty",
                        lua.create_function(register_xproperty)?)?;
     awesome_table.set("xkb_get_group_names",
@@ -150,67 +145,14 @@ fn register_xproperty<'lua>(lua: &'lua Lua,
     Ok(())
 }
 
-/// Get layout short names
+/// Get the description of the layouts in the keymap, in the form
+/// "pc+us+de:2+inet(evdev)".
 fn xkb_get_group_names<'lua>(lua: &'lua Lua, _: ()) -> rlua::Result<Value<'lua>> {
-    let xcb_con = lua.named_registry_value::<LightUserData>(XCB_CONNECTION_HANDLE)?
-                     .0;
-    unsafe {
-        let con = Connection::from_raw_conn(xcb_con as _);
-        let raw_con = con.get_raw_conn();
-        let id = xkb::ID_USE_CORE_KBD as _;
-        // The structure here looks weird because we need to ensure Connection
-        // isn't cleaned up even in the event of an error...
-        let names_r = {
-            let names_cookie = xkb::get_names_unchecked(&con, id, xkb::NAME_DETAIL_SYMBOLS);
-            names_cookie.get_reply()
-        };
-        mem::forget(con);
-        let names_r = match names_r {
-            Ok(names_r) => {
-                if names_r.ptr.is_null() {
-                    warn!("Failed to get xkb symbols name");
-                    return Ok(Value::Nil)
-                }
-                names_r
-            }
-            Err(err) => {
-                warn!("Failed to get xkb symbols name {:?}", err);
-                return Ok(Value::Nil)
-            }
-        };
-        let buffer = ffi::xkb::xcb_xkb_get_names_value_list(names_r.ptr);
-        if buffer.is_null() {
-            warn!("Returned buffer was NULL");
-            return Ok(Value::Nil)
-        }
-        let names_r_ptr = names_r.ptr;
-        if names_r_ptr.is_null() {
-            warn!("Name reply pointer was NULL");
-            return Ok(Value::Nil)
-        }
-        let mut names_list: ffi::xkb::xcb_xkb_get_names_value_list_t = mem::uninitialized();
-        xcb_xkb_get_names_value_list_unpack(buffer,
-                                            (*names_r_ptr).nTypes,
-                                            (*names_r_ptr).indicators,
-                                            (*names_r_ptr).virtualMods,
-                                            (*names_r_ptr).groupNames,
-                                            (*names_r_ptr).nKeys,
-                                            (*names_r_ptr).nKeyAliases,
-                                            (*names_r_ptr).nRadioGroups,
-                                            (*names_r_ptr).which,
-                                            &mut names_list);
-        let atom_name_c = ffi::xproto::xcb_get_atom_name_unchecked(raw_con, names_list.symbolsName);
-        let atom_name_r =
-            ffi::xproto::xcb_get_atom_name_reply(raw_con, atom_name_c, ptr::null_mut());
-        if atom_name_r.is_null() {
-            warn!("Failed to get atom symbols name");
-            return Ok(Value::Nil)
-        }
-        let name_c = ffi::xproto::xcb_get_atom_name_name(atom_name_r);
-        CStr::from_ptr(name_c).to_string_lossy()
-                              .into_owned()
-                              .to_lua(lua)
+    let symbols = XKB.lock().expect("Xkb was poisoned").symbols.clone();
+    if symbols.is_empty() {
+        return Ok(Value::Nil)
     }
+    symbols.to_lua(lua)
 }
 
 /// Query & set information about the systray
@@ -272,6 +214,63 @@ fn set_preferred_icon_size(lua: &Lua, val: u32) -> rlua::Result<()> {
     Ok(())
 }
 
+/// Sets how the keyboard focus follows the pointer.
+///
+/// The policy is one of "click", "sloppy" or "mouse". The optional delay is
+/// how many milliseconds the pointer has to rest on a client before it is
+/// focused.
+fn set_focus_policy(_: &Lua, (policy, delay): (String, Option<u32>)) -> rlua::Result<()> {
+    let policy = FocusPolicy::from_str(policy.as_str())
+        .ok_or(rlua::Error::RuntimeError(format!("{} is not a valid focus policy", policy)))?;
+    let delay = Duration::from_millis(delay.unwrap_or(0) as _);
+    compositor::set_focus_policy(policy, delay);
+    Ok(())
+}
+
+/// Sets the xcursor theme and size of the cursor.
+///
+/// If the theme is nil the default theme is used, if the size is nil the
+/// current size is kept.
+fn set_cursor_theme(_: &Lua, (theme, size): (Option<String>, Option<u32>)) -> rlua::Result<()> {
+    compositor::set_cursor_theme(theme, size);
+    Ok(())
+}
+
+/// Sets how many times a second a held key repeats, and how many
+/// milliseconds it has to be held before it starts repeating.
+///
+/// A rate of 0 disables key repeat.
+fn set_keyboard_repeat(_: &Lua, (rate, delay): (i32, Option<i32>)) -> rlua::Result<()> {
+    if rate < 0 || delay.unwrap_or(0) < 0 {
+        return Err(rlua::Error::RuntimeError("Key repeat rate and delay can't be negative"
+                                                 .into()))
+    }
+    let delay = delay.unwrap_or(RepeatInfo::default().delay);
+    compositor::set_repeat_info(RepeatInfo { rate, delay });
+    Ok(())
+}
+
+/// Sets after how many seconds of inactivity `idle::start` is emitted.
+///
+/// Each timeout gets its own `idle::start`, with the timeout as argument,
+/// and an `idle::stop` once the user is active again.
+fn set_idle_timeouts(_: &Lua, timeouts: Vec<u64>) -> rlua::Result<()> {
+    if timeouts.contains(&0) {
+        return Err(rlua::Error::RuntimeError("Idle timeouts must be positive".into()))
+    }
+    compositor::set_idle_timeouts(timeouts);
+    Ok(())
+}
+
+/// Gives the pointer back to the user when a client has locked it in place
+/// or confined it, e.g from an escape key binding in `root.keys`.
+///
+/// The client gets the pointer back once it is focused again.
+fn unconstrain_pointer(_: &Lua, _: ()) -> rlua::Result<()> {
+    compositor::release_pointer_constraint();
+    Ok(())
+}
+
 fn quit(_: &Lua, _: ()) -> rlua::Result<()> {
     ::wlroots::terminate();
     ::awesome::lua::terminate();
@@ -293,24 +292,32 @@ fn get_xproperty(_: &Lua, _: Value) -> rlua::Result<()> {
     Ok(())
 }
 
-fn xkb_set_layout_group(_: &Lua, _group: i32) -> rlua::Result<()> {
-    warn!("xkb_set_layout_group not supported; Wait until wlroots");
+/// Switch the keyboards to another layout group.
+fn xkb_set_layout_group(_: &Lua, group: u32) -> rlua::Result<()> {
+    compositor::set_layout_group(group);
     Ok(())
 }
 
-fn xkb_get_layout_group<'lua>(lua: &'lua Lua, _: ()) -> rlua::Result<Value<'lua>> {
-    use xcb::ffi::xkb;
-    unsafe {
-        let raw_con = lua.named_registry_value::<LightUserData>(XCB_CONNECTION_HANDLE)?
-                         .0 as _;
-        let state_c = xkb::xcb_xkb_get_state_unchecked(raw_con, xkb::XCB_XKB_ID_USE_CORE_KBD as _);
-        let state_r = xkb::xcb_xkb_get_state_reply(raw_con, state_c, ptr::null_mut());
-        if state_r.is_null() {
-            warn!("State reply was NULL");
-            return Ok(Value::Nil)
-        }
-        (*state_r).group.to_lua(lua)
-    }
+fn xkb_get_layout_group(_: &Lua, _: ()) -> rlua::Result<u32> {
+    Ok(XKB.lock().expect("Xkb was poisoned").group)
+}
+
+/// Sets the xkb rules, model, layout, variant and options of the keymap that
+/// is used by every keyboard.
+///
+/// Missing names use the xkb defaults.
+fn set_xkb_layout(_: &Lua, names: Table) -> rlua::Result<()> {
+    let config = XkbConfig { rules: names.get::<_, Option<String>>("rules")?
+                                         .unwrap_or_default(),
+                             model: names.get::<_, Option<String>>("model")?
+                                         .unwrap_or_default(),
+                             layout: names.get::<_, Option<String>>("layout")?
+                                          .unwrap_or_default(),
+                             variant: names.get::<_, Option<String>>("variant")?
+                                           .unwrap_or_default(),
+                             options: names.get("options")? };
+    compositor::set_xkb_config(config);
+    Ok(())
 }
 
 fn xrdb_get_value(_lua: &Lua,
@@ -378,3 +385,60 @@ pub fn load_surface_from_pixbuf(pixbuf: Pixbuf) -> ImageSurface {
     }
     surface
 }
+
+/// Let Lua know that the keymap of the keyboards was changed.
+pub fn xkb_map_changed() {
+    emit_global("xkb::map_changed", ())
+}
+
+/// Let Lua know that the keyboards switched to another layout group.
+pub fn xkb_group_changed() {
+    emit_global("xkb::group_changed", ())
+}
+
+/// Let Lua know a swipe gesture finished, and how far the fingers moved.
+pub fn gesture_swipe(fingers: u32, dx: f64, dy: f64) {
+    emit_global("gesture::swipe", (fingers, dx, dy))
+}
+
+/// Let Lua know a pinch gesture finished.
+///
+/// The scale is relative to where the fingers started, and the rotation is
+/// in degrees clockwise.
+pub fn gesture_pinch(fingers: u32, scale: f64, rotation: f64) {
+    emit_global("gesture::pinch", (fingers, scale, rotation))
+}
+
+/// Let Lua know the user has been idle for the timeout.
+pub fn idle_start(timeout: u64) {
+    emit_global("idle::start", timeout)
+}
+
+/// Let Lua know the user is no longer idle after the timeout passed.
+pub fn idle_stop(timeout: u64) {
+    emit_global("idle::stop", timeout)
+}
+
+/// Let Lua know the text of the selection with the name ("primary" or
+/// "clipboard") changed.
+pub fn selection_changed(name: &'static str) {
+    emit_global("selection::changed", name)
+}
+
+/// Let Lua know that new content was captured for `root.content`,
+/// `screen.content` or `client.content`.
+pub fn content_changed() {
+    emit_global("content::changed", ())
+}
+
+/// Emit a global signal on behalf of the compositor.
+fn emit_global<A>(name: &'static str, args: A)
+    where A: for<'lua> ToLuaMulti<'lua> + Clone
+{
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 if let Err(err) = signal::global_emit_signal(&*lua, (name.into(), args)) {
+                     warn!("Could not emit {}: {}", name, err);
+                 }
+             })
+}
@@ -5,13 +5,13 @@ use super::signal;
 use rlua::{self, AnyUserData, Lua, Table, ToLua, UserData, UserDataMethods, Value};
 use std::default::Default;
 use std::fmt::{self, Display, Formatter};
-use wlroots::events::key_events::Key;
 use xcb::ffi::xproto::xcb_button_t;
 
 #[derive(Clone, Debug)]
 pub struct ButtonState {
     button: xcb_button_t,
-    modifiers: Vec<Key>
+    /// The mask of modifiers that have to be held for this button.
+    modifiers: u32
 }
 
 #[derive(Clone, Debug)]
@@ -26,7 +26,7 @@ impl Display for ButtonState {
 impl Default for ButtonState {
     fn default() -> Self {
         ButtonState { button: xcb_button_t::default(),
-                      modifiers: Vec::new() }
+                      modifiers: 0 }
     }
 }
 
@@ -54,7 +54,7 @@ impl<'lua> Button<'lua> {
         Ok(())
     }
 
-    pub fn modifiers(&self) -> rlua::Result<Vec<Key>> {
+    pub fn modifiers(&self) -> rlua::Result<u32> {
         let button = self.state()?;
         Ok(button.modifiers)
     }
@@ -65,6 +65,17 @@ impl<'lua> Button<'lua> {
         button.modifiers = mods_to_rust(mods)?;
         Ok(())
     }
+
+    /// Determine if this button binding is triggered by pressing the X
+    /// button number `button` while the modifiers in `mods` are active.
+    ///
+    /// A button of 0 matches any button.
+    pub fn matches(&self, button: u32, mods: u32) -> rlua::Result<bool> {
+        use lua::mods_match;
+        let state = self.state()?;
+        Ok((state.button == 0 || state.button as u32 == button)
+           && mods_match(state.modifiers, mods))
+    }
 }
 
 impl<'lua> ToLua<'lua> for Button<'lua> {
@@ -121,5 +132,34 @@ fn set_modifiers<'lua>(lua: &'lua Lua,
 
 fn get_modifiers<'lua>(lua: &'lua Lua, obj: AnyUserData<'lua>) -> rlua::Result<Value<'lua>> {
     use lua::mods_to_lua;
-    mods_to_lua(lua, &Button::cast(obj.into())?.modifiers()?).map(Value::Table)
+    mods_to_lua(lua, Button::cast(obj.into())?.modifiers()?).map(Value::Table)
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::button;
+    use rlua::Lua;
+
+    #[test]
+    fn button_modifiers_are_real_modifiers() {
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        lua.eval(
+            r#"
+local b = button{ modifiers = { "Super", "Shift" } }
+assert(#b.modifiers == 2)
+assert(b.modifiers[1] == "Shift")
+assert(b.modifiers[2] == "Mod4")
+"#,
+            None
+        ).unwrap()
+    }
+
+    #[test]
+    fn button_invalid_modifier() {
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        assert!(lua.eval::<()>(r#"button{ modifiers = { "NotAModifier" } }"#, None)
+                   .is_err())
+    }
 }
@@ -1,34 +1,73 @@
 //! TODO Fill in
-use super::class::{Class, ClassBuilder};
-use super::object::{Object, Objectable};
-use rlua::{self, Lua, Table, ToLua, UserData, Value};
+use super::class::{self, Class, ClassBuilder};
+use super::drawable::Drawable;
+use super::object::{self, Object, Objectable};
+use super::property::Property;
+use super::signal;
+use awesome::{CONTENTS, LUA};
+use awesome::lua::surface_to_lua;
+use compositor::{self, ContentSource, TitlebarSide};
+use rlua::{self, AnyUserData, Integer, Lua, Table, ToLua, UserData, UserDataMethods, Value};
 use std::default::Default;
 use std::fmt::{self, Display, Formatter};
+use wlroots::Area;
+
+/// The clients that represent a view in the compositor, in the order they
+/// were managed.
+pub const CLIENTS_HANDLE: &'static str = "__clients";
 
 #[derive(Clone, Debug)]
 pub struct ClientState {
-    // TODO Fill in
-    dummy: i32
+    /// The id of the view in the compositor that this client represents.
+    view_id: Option<usize>,
+    /// The size of the titlebars, in the order of `TitlebarSide`.
+    titlebars: [i32; 4],
+    /// If the compositor draws the titlebars, or nil to let the client pick.
+    titlebars_enabled: Option<bool>,
+    /// If the client didn't ask the compositor to decorate it.
+    requests_no_titlebar: bool,
+    border_width: u32,
+    /// The color of the border, as Lua set it.
+    border_color: String,
+    opacity: f64
 }
 
 pub struct Client<'lua>(Object<'lua>);
 
 impl Default for ClientState {
     fn default() -> Self {
-        ClientState { dummy: 0 }
+        ClientState { view_id: None,
+                      titlebars: [0; 4],
+                      titlebars_enabled: None,
+                      requests_no_titlebar: true,
+                      border_width: 0,
+                      border_color: "#000000".into(),
+                      opacity: 1.0 }
     }
 }
 
-/* This is currently unused.
- * TODO: Figure out if this will be needed later.
-
-impl <'lua> Client<'lua> {
-    fn new(lua: &Lua) -> rlua::Result<Object> {
+impl<'lua> Client<'lua> {
+    fn new(lua: &'lua Lua, view_id: usize) -> rlua::Result<Object<'lua>> {
         let class = class::class_setup(lua, "client")?;
-        Ok(Client::allocate(lua, class)?.build())
+        let table = lua.create_table()?;
+        table.set("titlebar_top",
+                  lua.create_function(|lua, args| titlebar(lua, args, TitlebarSide::Top))?)?;
+        table.set("titlebar_right",
+                  lua.create_function(|lua, args| titlebar(lua, args, TitlebarSide::Right))?)?;
+        table.set("titlebar_bottom",
+                  lua.create_function(|lua, args| titlebar(lua, args, TitlebarSide::Bottom))?)?;
+        table.set("titlebar_left",
+                  lua.create_function(|lua, args| titlebar(lua, args, TitlebarSide::Left))?)?;
+        let builder = Client::allocate(lua, class)?.add_to_meta(table)?;
+        let mut client = Client::cast(builder.build())?;
+        client.get_object_mut()?.view_id = Some(view_id);
+        Ok(client.0)
+    }
+
+    pub fn view_id(&self) -> rlua::Result<Option<usize>> {
+        Ok(self.state()?.view_id)
     }
 }
-*/
 
 impl Display for ClientState {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
@@ -42,11 +81,17 @@ impl<'lua> ToLua<'lua> for Client<'lua> {
     }
 }
 
-impl UserData for ClientState {}
+impl UserData for ClientState {
+    fn add_methods(methods: &mut UserDataMethods<Self>) {
+        object::default_add_methods(methods);
+    }
+}
 
 pub fn init(lua: &Lua) -> rlua::Result<Class> {
-    method_setup(lua, Class::builder(lua, "client", None)?)?.save_class("client")?
-                                                            .build()
+    lua.set_named_registry_value(CLIENTS_HANDLE, lua.create_table()?)?;
+    property_setup(lua, method_setup(lua, Class::builder(lua, "client", None)?)?)?
+        .save_class("client")?
+        .build()
 }
 
 fn method_setup<'lua>(lua: &'lua Lua,
@@ -58,8 +103,474 @@ fn method_setup<'lua>(lua: &'lua Lua,
            .method("get".into(), lua.create_function(dummy_table)?)
 }
 
+fn property_setup<'lua>(lua: &'lua Lua,
+                        builder: ClassBuilder<'lua>)
+                        -> rlua::Result<ClassBuilder<'lua>> {
+    builder.property(Property::new("content".into(),
+                                   None,
+                                   Some(lua.create_function(get_content)?),
+                                   None))?
+           .property(Property::new("titlebars_enabled".into(),
+                                   Some(lua.create_function(set_titlebars_enabled)?),
+                                   Some(lua.create_function(get_titlebars_enabled)?),
+                                   Some(lua.create_function(set_titlebars_enabled)?)))?
+           .property(Property::new("requests_no_titlebar".into(),
+                                   None,
+                                   Some(lua.create_function(get_requests_no_titlebar)?),
+                                   None))?
+           .property(Property::new("border_width".into(),
+                                   Some(lua.create_function(set_border_width)?),
+                                   Some(lua.create_function(get_border_width)?),
+                                   Some(lua.create_function(set_border_width)?)))?
+           .property(Property::new("border_color".into(),
+                                   Some(lua.create_function(set_border_color)?),
+                                   Some(lua.create_function(get_border_color)?),
+                                   Some(lua.create_function(set_border_color)?)))?
+           .property(Property::new("opacity".into(),
+                                   Some(lua.create_function(set_opacity)?),
+                                   Some(lua.create_function(get_opacity)?),
+                                   Some(lua.create_function(set_opacity)?)))
+}
+
 impl_objectable!(Client, ClientState);
 
+/// Gets the client without what is above it as a cairo surface, as it was
+/// when it was last captured, or nil if it wasn't captured yet.
+///
+/// Reading it asks for a new capture, which `content::changed` announces.
+fn get_content<'lua>(lua: &'lua Lua, obj: AnyUserData<'lua>) -> rlua::Result<Value<'lua>> {
+    let view_id = match obj.borrow::<ClientState>()?.view_id {
+        Some(view_id) => view_id,
+        None => return Ok(Value::Nil)
+    };
+    surface_to_lua(lua, compositor::content(ContentSource::View(view_id)))
+}
+
+fn get_titlebars_enabled<'lua>(_: &'lua Lua, obj: AnyUserData<'lua>) -> rlua::Result<Option<bool>> {
+    Ok(obj.borrow::<ClientState>()?.titlebars_enabled)
+}
+
+/// Makes the compositor draw the titlebars of the client, or makes the client
+/// decorate itself. Setting it to nil lets the client pick.
+fn set_titlebars_enabled<'lua>(lua: &'lua Lua,
+                               (obj, enabled): (AnyUserData<'lua>, Option<bool>))
+                               -> rlua::Result<()> {
+    let mut client = Client::cast(obj.into())?;
+    let view_id = {
+        let mut state = client.get_object_mut()?;
+        if state.titlebars_enabled == enabled {
+            return Ok(())
+        }
+        state.titlebars_enabled = enabled;
+        state.view_id
+    };
+    if let Some(view_id) = view_id {
+        compositor::set_server_side_decorations(view_id, enabled);
+    }
+    signal::emit_object_signal(lua, client.0, "property::titlebars_enabled".into(), ())
+}
+
+fn get_requests_no_titlebar<'lua>(_: &'lua Lua, obj: AnyUserData<'lua>) -> rlua::Result<bool> {
+    Ok(obj.borrow::<ClientState>()?.requests_no_titlebar)
+}
+
+fn get_border_width<'lua>(_: &'lua Lua, obj: AnyUserData<'lua>) -> rlua::Result<u32> {
+    Ok(obj.borrow::<ClientState>()?.border_width)
+}
+
+fn set_border_width<'lua>(lua: &'lua Lua,
+                          (obj, width): (AnyUserData<'lua>, u32))
+                          -> rlua::Result<()> {
+    let mut client = Client::cast(obj.into())?;
+    let view_id = {
+        let mut state = client.get_object_mut()?;
+        if state.border_width == width {
+            return Ok(())
+        }
+        state.border_width = width;
+        state.view_id
+    };
+    if let Some(view_id) = view_id {
+        compositor::set_border_width(view_id, width as i32);
+    }
+    signal::emit_object_signal(lua, client.0, "property::border_width".into(), ())
+}
+
+fn get_border_color<'lua>(_: &'lua Lua, obj: AnyUserData<'lua>) -> rlua::Result<String> {
+    Ok(obj.borrow::<ClientState>()?.border_color.clone())
+}
+
+/// Sets the color of the border, e.g "#ff0000" or "#ff000080" to make it half
+/// transparent.
+fn set_border_color<'lua>(lua: &'lua Lua,
+                          (obj, color): (AnyUserData<'lua>, String))
+                          -> rlua::Result<()> {
+    let rgba = ::lua::color_to_rust(&color)?;
+    let mut client = Client::cast(obj.into())?;
+    let view_id = {
+        let mut state = client.get_object_mut()?;
+        if state.border_color == color {
+            return Ok(())
+        }
+        state.border_color = color;
+        state.view_id
+    };
+    if let Some(view_id) = view_id {
+        compositor::set_border_color(view_id, rgba);
+    }
+    signal::emit_object_signal(lua, client.0, "property::border_color".into(), ())
+}
+
+fn get_opacity<'lua>(_: &'lua Lua, obj: AnyUserData<'lua>) -> rlua::Result<f64> {
+    Ok(obj.borrow::<ClientState>()?.opacity)
+}
+
+/// Sets how opaque the client is, from 0 to 1. Setting it to nil makes it
+/// opaque.
+fn set_opacity<'lua>(lua: &'lua Lua,
+                     (obj, opacity): (AnyUserData<'lua>, Option<f64>))
+                     -> rlua::Result<()> {
+    let opacity = opacity.unwrap_or(1.0);
+    if !opacity.is_finite() || opacity < 0.0 || opacity > 1.0 {
+        return Err(rlua::Error::RuntimeError(format!("{} is not between 0 and 1", opacity)))
+    }
+    let mut client = Client::cast(obj.into())?;
+    let view_id = {
+        let mut state = client.get_object_mut()?;
+        if state.opacity == opacity {
+            return Ok(())
+        }
+        state.opacity = opacity;
+        state.view_id
+    };
+    if let Some(view_id) = view_id {
+        compositor::set_opacity(view_id, opacity as f32);
+    }
+    signal::emit_object_signal(lua, client.0, "property::opacity".into(), ())
+}
+
+/// Gets the drawable of the titlebar on the side and its size, resizing it
+/// first if a size is given.
+///
+/// The titlebar is only drawn if the compositor decorates the client.
+fn titlebar<'lua>(lua: &'lua Lua,
+                  (obj, size): (AnyUserData<'lua>, Option<i32>),
+                  side: TitlebarSide)
+                  -> rlua::Result<(AnyUserData<'lua>, i32)> {
+    let mut client = Client::cast(obj.into())?;
+    if let Some(size) = size {
+        if size < 0 {
+            return Err(rlua::Error::RuntimeError("titlebar size can't be negative".into()))
+        }
+        let view_id = {
+            let mut state = client.get_object_mut()?;
+            state.titlebars[side as usize] = size;
+            state.view_id
+        };
+        if let Some(view_id) = view_id {
+            compositor::set_titlebar_size(view_id, side, size);
+        }
+    }
+    let size = client.state()?.titlebars[side as usize];
+    Ok((titlebar_drawable(lua, &client, side)?, size))
+}
+
+/// Gets the drawable of the titlebar on the side, which is created the first
+/// time it's needed.
+fn titlebar_drawable<'lua>(lua: &'lua Lua,
+                           client: &Client<'lua>,
+                           side: TitlebarSide)
+                           -> rlua::Result<AnyUserData<'lua>> {
+    let table = client.0.table()?;
+    let key = format!("__titlebar_{}", side.as_str());
+    if let Value::UserData(drawable) = table.raw_get::<_, Value>(key.clone())? {
+        return Ok(drawable)
+    }
+    let drawable = Drawable::new(lua)?.object;
+    table.raw_set(key, drawable.clone())?;
+    Ok(drawable)
+}
+
 fn dummy_table<'lua>(lua: &'lua Lua, _: rlua::Value) -> rlua::Result<Table<'lua>> {
     Ok(lua.create_table()?)
 }
+
+/// Gets the client that represents the view with the given id.
+pub fn client_by_view<'lua>(lua: &'lua Lua, view_id: usize) -> rlua::Result<Option<Client<'lua>>> {
+    let clients = lua.named_registry_value::<Vec<AnyUserData>>(CLIENTS_HANDLE)?;
+    for client in clients {
+        let client = Client::cast(client.into())?;
+        if client.view_id()? == Some(view_id) {
+            return Ok(Some(client))
+        }
+    }
+    Ok(None)
+}
+
+/// Creates a client for a view that was just created by the compositor.
+pub fn manage_client(view_id: usize) {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 if let Err(err) = manage(&*lua, view_id) {
+                     warn!("Could not manage client for view {}: {}", view_id, err);
+                 }
+             })
+}
+
+/// Removes the client of a view that was destroyed by the compositor.
+pub fn unmanage_client(view_id: usize) {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 if let Err(err) = unmanage(&*lua, view_id) {
+                     warn!("Could not unmanage client for view {}: {}", view_id, err);
+                 }
+             })
+}
+
+/// Lets Lua know if the client of the view asked the compositor to draw its
+/// titlebars.
+pub fn client_requests_no_titlebar(view_id: usize, requests_no_titlebar: bool) {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 if let Err(err) = set_requests_no_titlebar(&*lua, view_id, requests_no_titlebar) {
+                     warn!("Could not update titlebar request of view {}: {}", view_id, err);
+                 }
+             })
+}
+
+/// Emits a button press or release on the titlebar of the view.
+///
+/// The coordinates are relative to the titlebar.
+pub fn titlebar_button(view_id: usize,
+                       side: TitlebarSide,
+                       x: f64,
+                       y: f64,
+                       button: u32,
+                       pressed: bool,
+                       mods: u32) {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 if let Err(err) = emit_titlebar_button(&*lua,
+                                                        view_id,
+                                                        side,
+                                                        (x, y),
+                                                        button,
+                                                        pressed,
+                                                        mods)
+                 {
+                     warn!("Could not send button to titlebar: {}", err);
+                 }
+             })
+}
+
+/// Emits a mouse signal (e.g `mouse::enter`) on the titlebar of the view.
+///
+/// The coordinates are relative to the titlebar, and are only given for
+/// `mouse::move`.
+pub fn titlebar_mouse(view_id: usize,
+                      side: TitlebarSide,
+                      name: &'static str,
+                      coords: Option<(f64, f64)>) {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 if let Err(err) = emit_titlebar_mouse(&*lua, view_id, side, name, coords) {
+                     warn!("Could not send {} to titlebar: {}", name, err);
+                 }
+             })
+}
+
+/// Resizes the titlebar drawable of the view to the area, relative to the
+/// client, as only the compositor knows how big the client is.
+///
+/// Returns the id of the drawable, to draw what Lua draws in it.
+pub fn titlebar_resized(view_id: usize, side: TitlebarSide, area: Area) -> Option<usize> {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 match resize_titlebar(&*lua, view_id, side, area) {
+                     Ok(id) => id,
+                     Err(err) => {
+                         warn!("Could not resize titlebar of view {}: {}", view_id, err);
+                         None
+                     }
+                 }
+             })
+}
+
+fn set_requests_no_titlebar(lua: &Lua, view_id: usize, value: bool) -> rlua::Result<()> {
+    let mut client = match client_by_view(lua, view_id)? {
+        Some(client) => client,
+        None => return Ok(())
+    };
+    {
+        let mut state = client.get_object_mut()?;
+        if state.requests_no_titlebar == value {
+            return Ok(())
+        }
+        state.requests_no_titlebar = value;
+    }
+    signal::emit_object_signal(lua, client.0, "property::requests_no_titlebar".into(), ())
+}
+
+fn emit_titlebar_button(lua: &Lua,
+                        view_id: usize,
+                        side: TitlebarSide,
+                        (x, y): (f64, f64),
+                        button: u32,
+                        pressed: bool,
+                        mods: u32)
+                        -> rlua::Result<()> {
+    let client = match client_by_view(lua, view_id)? {
+        Some(client) => client,
+        None => return Ok(())
+    };
+    let drawable = titlebar_drawable(lua, &client, side)?;
+    let name = if pressed { "button::press" } else { "button::release" };
+    let args = (x as i32, y as i32, ::lua::button_to_lua(button), ::lua::mods_to_lua(lua, mods)?);
+    signal::emit_object_signal(lua, drawable.into(), name.into(), args)
+}
+
+fn emit_titlebar_mouse(lua: &Lua,
+                       view_id: usize,
+                       side: TitlebarSide,
+                       name: &'static str,
+                       coords: Option<(f64, f64)>)
+                       -> rlua::Result<()> {
+    let client = match client_by_view(lua, view_id)? {
+        Some(client) => client,
+        None => return Ok(())
+    };
+    let drawable = titlebar_drawable(lua, &client, side)?;
+    match coords {
+        Some((x, y)) => {
+            signal::emit_object_signal(lua, drawable.into(), name.into(), (x as i32, y as i32))
+        }
+        None => signal::emit_object_signal(lua, drawable.into(), name.into(), ())
+    }
+}
+
+fn resize_titlebar(lua: &Lua,
+                   view_id: usize,
+                   side: TitlebarSide,
+                   area: Area)
+                   -> rlua::Result<Option<usize>> {
+    let client = match client_by_view(lua, view_id)? {
+        Some(client) => client,
+        None => return Ok(None)
+    };
+    let drawable_object = titlebar_drawable(lua, &client, side)?;
+    let mut drawable = Drawable::cast(drawable_object.clone().into())?;
+    let geometry = drawable.get_geometry()?;
+    if geometry != area {
+        drawable.set_geometry(area)?;
+        // A new surface was allocated, which Lua has to draw again.
+        if geometry.size != area.size {
+            signal::emit_object_signal(lua,
+                                       drawable_object.into(),
+                                       "property::surface".into(),
+                                       ())?;
+        }
+    }
+    Ok(Some(drawable.id()?))
+}
+
+fn manage(lua: &Lua, view_id: usize) -> rlua::Result<()> {
+    let client = Client::new(lua, view_id)?;
+    let clients = lua.named_registry_value::<Table>(CLIENTS_HANDLE)?;
+    let index = clients.len()? + 1;
+    clients.set(index, client)
+}
+
+fn unmanage(lua: &Lua, view_id: usize) -> rlua::Result<()> {
+    CONTENTS.lock()
+            .expect("Contents was poisoned")
+            .retain(|&(ref source, _)| *source != ContentSource::View(view_id));
+    let clients = lua.named_registry_value::<Table>(CLIENTS_HANDLE)?;
+    let clients_count = clients.len()?;
+    for pair in clients.clone().pairs::<Integer, AnyUserData>() {
+        let (key, value) = pair?;
+        if value.borrow::<ClientState>()?.view_id == Some(view_id) {
+            // Now remove this by shifting everything down...
+            for index in key..clients_count {
+                clients.set(index, clients.get::<_, Value>(index + 1)?)?;
+            }
+            clients.set(clients_count, Value::Nil)?;
+            break
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{client, drawable};
+    use super::Client;
+    use rlua::Lua;
+
+    #[test]
+    fn client_titlebars_default() {
+        let lua = Lua::new();
+        drawable::init(&lua).unwrap();
+        client::init(&lua).unwrap();
+        lua.globals().set("c", Client::new(&lua, 0).unwrap()).unwrap();
+        lua.eval(
+            r#"
+assert(c.requests_no_titlebar == true)
+assert(c.titlebars_enabled == nil)
+local drawable, size = c:titlebar_top()
+assert(drawable ~= nil)
+assert(size == 0)
+assert(rawequal(drawable, c:titlebar_top()))
+assert(not rawequal(drawable, c:titlebar_bottom()))
+"#,
+            None
+        ).unwrap()
+    }
+
+    #[test]
+    fn client_border_properties() {
+        let lua = Lua::new();
+        drawable::init(&lua).unwrap();
+        client::init(&lua).unwrap();
+        lua.globals().set("c", Client::new(&lua, 0).unwrap()).unwrap();
+        lua.eval(
+            r#"
+assert(c.border_width == 0)
+assert(c.border_color == "#000000")
+local changed = {}
+c:connect_signal("property::border_width", function() changed.width = true end)
+c:connect_signal("property::border_color", function() changed.color = true end)
+c.border_width = 2
+c.border_color = "#ff000080"
+assert(c.border_width == 2)
+assert(c.border_color == "#ff000080")
+assert(changed.width and changed.color)
+assert(not pcall(function() c.border_color = "red" end))
+assert(c.border_color == "#ff000080")
+"#,
+            None
+        ).unwrap()
+    }
+
+    #[test]
+    fn client_opacity() {
+        let lua = Lua::new();
+        drawable::init(&lua).unwrap();
+        client::init(&lua).unwrap();
+        lua.globals().set("c", Client::new(&lua, 0).unwrap()).unwrap();
+        lua.eval(
+            r#"
+assert(c.opacity == 1)
+local changed = false
+c:connect_signal("property::opacity", function() changed = true end)
+c.opacity = 0.5
+assert(c.opacity == 0.5)
+assert(changed)
+assert(not pcall(function() c.opacity = 2 end))
+assert(not pcall(function() c.opacity = 0/0 end))
+c.opacity = nil
+assert(c.opacity == 1)
+"#,
+            None
+        ).unwrap()
+    }
+}
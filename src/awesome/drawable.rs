@@ -3,15 +3,22 @@
 use super::class::{self, Class};
 use super::object::{self, Object, Objectable};
 use super::property::Property;
-use cairo::{Format, ImageSurface};
+use cairo::{Context, Format, ImageSurface};
+use compositor;
 use glib::translate::ToGlibPtr;
+use ipc::Content;
 use rlua::{self, AnyUserData, LightUserData, Lua, Table, ToLua, UserData, UserDataMethods, Value};
 use std::default::Default;
 use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 use wlroots::{Area, Origin, Size};
 
+static NEXT_DRAWABLE_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
 #[derive(Clone, Debug)]
 pub struct DrawableState {
+    /// Identifies what the drawable drew to the compositor.
+    id: usize,
     pub surface: Option<ImageSurface>,
     geo: Area,
     // TODO Use this to determine whether we draw this or not
@@ -24,7 +31,8 @@ impl_objectable!(Drawable, DrawableState);
 
 impl Default for DrawableState {
     fn default() -> Self {
-        DrawableState { surface: None,
+        DrawableState { id: NEXT_DRAWABLE_ID.fetch_add(1, Ordering::SeqCst),
+                        surface: None,
                         geo: Area::default(),
                         refreshed: false }
     }
@@ -41,6 +49,10 @@ impl<'lua> Drawable<'lua> {
         Ok(builder.add_to_meta(table)?.build())
     }
 
+    pub fn id(&self) -> rlua::Result<usize> {
+        Ok(self.state()?.id)
+    }
+
     pub fn get_geometry(&self) -> rlua::Result<Area> {
         let drawable = self.state()?;
         Ok(drawable.geo)
@@ -67,12 +79,16 @@ impl<'lua> Drawable<'lua> {
     }
 
     /// Sets the geometry, and allocates a new surface.
+    ///
+    /// The compositor stops drawing what was drawn before until the new
+    /// surface is refreshed.
     pub fn set_geometry(&mut self, geometry: Area) -> rlua::Result<()> {
         use rlua::Error::RuntimeError;
         let mut drawable = self.get_object_mut()?;
         let size_changed = drawable.geo != geometry;
         drawable.geo = geometry;
         if size_changed {
+            compositor::drawable_refreshed(drawable.id, None);
             drawable.surface = None;
             drawable.refreshed = false;
             let size: Size = geometry.size;
@@ -87,12 +103,58 @@ impl<'lua> Drawable<'lua> {
         Ok(())
     }
 
-    /// Signals that the drawable's surface was updated.
+    /// Gets a copy of the surface, for the compositor to draw.
+    ///
+    /// Lua holds references to the surface, so its pixels can only be read
+    /// from a copy.
+    fn copy_surface(&self) -> rlua::Result<Option<ImageSurface>> {
+        use rlua::Error::RuntimeError;
+        let drawable = self.state()?;
+        let surface = match drawable.surface {
+            Some(surface) => surface,
+            None => return Ok(None)
+        };
+        let Size { width, height } = drawable.geo.size;
+        let copy = ImageSurface::create(Format::ARgb32, width, height)
+            .map_err(|err| RuntimeError(format!("Could not allocate {:?}", err)))?;
+        {
+            let cr = Context::new(&copy);
+            cr.set_source_surface(&surface, 0.0, 0.0);
+            cr.paint();
+        }
+        Ok(Some(copy))
+    }
+
+    /// Signals that the drawable's surface was updated, handing what was
+    /// drawn to the compositor.
     pub fn refresh(&mut self) -> rlua::Result<()> {
+        let pixels = self.pixels()?;
         let mut drawable = self.get_object_mut()?;
         drawable.refreshed = true;
+        compositor::drawable_refreshed(drawable.id, pixels);
         Ok(())
     }
+
+    /// Gets the pixels of the surface, without the padding of its rows.
+    fn pixels(&self) -> rlua::Result<Option<Content>> {
+        use rlua::Error::RuntimeError;
+        let mut copy = match self.copy_surface()? {
+            Some(copy) => copy,
+            None => return Ok(None)
+        };
+        let (width, height) = (copy.get_width(), copy.get_height());
+        let stride = copy.get_stride() as usize;
+        let row = width as usize * 4;
+        let data = copy.get_data()
+                       .map_err(|err| RuntimeError(format!("Could not read {:?}", err)))?;
+        let pixels = (0..height as usize).flat_map(|y| &data[y * stride..y * stride + row])
+                                         .cloned()
+                                         .collect();
+        Ok(Some(Content { width,
+                          height,
+                          data: pixels,
+                          fresh: false }))
+    }
 }
 
 impl Display for DrawableState {
@@ -3,7 +3,9 @@
 
 use super::drawable::Drawable;
 use super::property::Property;
-use rlua::{self, AnyUserData, Lua, Table, ToLua, UserData, UserDataMethods, Value};
+use super::signal;
+use awesome::LUA;
+use rlua::{self, AnyUserData, Integer, Lua, Table, ToLua, UserData, UserDataMethods, Value};
 use rlua::prelude::LuaInteger;
 use std::default::Default;
 use std::fmt::{self, Display, Formatter};
@@ -12,6 +14,9 @@ use wlroots::{Area, Origin, Size};
 use super::class::{self, Class, ClassBuilder};
 use super::object::{self, Object, ObjectBuilder, Objectable};
 
+/// The visible drawins, from bottom to top.
+pub const DRAWINS_HANDLE: &'static str = "__drawins";
+
 #[derive(Clone, Debug)]
 pub struct DrawinState {
     // Note that the drawable is stored in Lua.
@@ -20,7 +25,8 @@ pub struct DrawinState {
     visible: bool,
     cursor: String,
     geometry: Area,
-    geometry_dirty: bool
+    geometry_dirty: bool,
+    opacity: f64
 }
 
 #[derive(Clone, Debug)]
@@ -38,7 +44,8 @@ impl Default for DrawinState {
                       visible: false,
                       cursor: String::default(),
                       geometry: Area::default(),
-                      geometry_dirty: false }
+                      geometry_dirty: false,
+                      opacity: 1.0 }
     }
 }
 
@@ -118,6 +125,7 @@ impl<'lua> ToLua<'lua> for Drawin<'lua> {
 impl_objectable!(Drawin, DrawinState);
 
 pub fn init(lua: &Lua) -> rlua::Result<Class> {
+    lua.set_named_registry_value(DRAWINS_HANDLE, lua.create_table()?)?;
     property_setup(lua, method_setup(lua, Class::builder(lua, "drawin", None)?)?)?
         .save_class("drawin")?
         .build()
@@ -156,7 +164,11 @@ fn property_setup<'lua>(lua: &'lua Lua,
            .property(Property::new("visible".into(),
                                    Some(lua.create_function(set_visible)?),
                                    Some(lua.create_function(get_visible)?),
-                                   Some(lua.create_function(set_visible)?)))
+                                   Some(lua.create_function(set_visible)?)))?
+           .property(Property::new("opacity".into(),
+                                   Some(lua.create_function(set_opacity)?),
+                                   Some(lua.create_function(get_opacity)?),
+                                   Some(lua.create_function(set_opacity)?)))
 }
 
 fn object_setup<'lua>(lua: &'lua Lua,
@@ -171,8 +183,30 @@ fn object_setup<'lua>(lua: &'lua Lua,
     builder.add_to_meta(table)
 }
 
-fn set_visible<'lua>(_: &'lua Lua, (obj, visible): (AnyUserData<'lua>, bool)) -> rlua::Result<()> {
-    let mut drawin = Drawin::cast(obj.into())?;
+fn set_visible<'lua>(lua: &'lua Lua,
+                     (obj, visible): (AnyUserData<'lua>, bool))
+                     -> rlua::Result<()> {
+    let mut drawin = Drawin::cast(obj.clone().into())?;
+    if drawin.get_visible()? != visible {
+        let drawins = lua.named_registry_value::<Table>(DRAWINS_HANDLE)?;
+        let drawins_count = drawins.len()?;
+        if visible {
+            drawins.set(drawins_count + 1, obj.clone())?;
+        } else {
+            // Find and remove the drawin from the list of visible drawins
+            let drawin_ref = &*obj.borrow::<DrawinState>()? as *const _;
+            for pair in drawins.clone().pairs::<Integer, AnyUserData>() {
+                let (key, value) = pair?;
+                if drawin_ref == &*value.borrow::<DrawinState>()? as *const _ {
+                    for index in key..drawins_count {
+                        drawins.set(index, drawins.get::<_, Value>(index + 1)?)?;
+                    }
+                    drawins.set(drawins_count, Value::Nil)?;
+                    break
+                }
+            }
+        }
+    }
     drawin.set_visible(visible)
     // TODO signal
 }
@@ -183,6 +217,31 @@ fn get_visible<'lua>(_: &'lua Lua, obj: AnyUserData<'lua>) -> rlua::Result<bool>
     // TODO signal
 }
 
+fn get_opacity<'lua>(_: &'lua Lua, drawin: AnyUserData<'lua>) -> rlua::Result<f64> {
+    let drawin = Drawin::cast(drawin.into())?;
+    Ok(drawin.state()?.opacity)
+}
+
+/// Sets how opaque the drawin is, from 0 to 1. Setting it to nil makes it
+/// opaque.
+fn set_opacity<'lua>(lua: &'lua Lua,
+                     (obj, opacity): (AnyUserData<'lua>, Option<f64>))
+                     -> rlua::Result<()> {
+    let opacity = opacity.unwrap_or(1.0);
+    if !opacity.is_finite() || opacity < 0.0 || opacity > 1.0 {
+        return Err(rlua::Error::RuntimeError(format!("{} is not between 0 and 1", opacity)))
+    }
+    let mut drawin = Drawin::cast(obj.clone().into())?;
+    {
+        let mut state = drawin.get_object_mut()?;
+        if state.opacity == opacity {
+            return Ok(())
+        }
+        state.opacity = opacity;
+    }
+    signal::emit_object_signal(lua, obj.into(), "property::opacity".into(), ())
+}
+
 fn drawin_geometry<'lua>(lua: &'lua Lua,
                          (drawin, geometry): (AnyUserData<'lua>, Option<Table<'lua>>))
                          -> rlua::Result<Table<'lua>> {
@@ -283,3 +342,97 @@ fn drawin_struts<'lua>(lua: &'lua Lua, _drawin: AnyUserData<'lua>) -> rlua::Resu
     res.set("bottom", 0)?;
     Ok(res)
 }
+
+/// Gets the top most visible drawin at the given layout coordinates.
+pub fn drawin_at<'lua>(lua: &'lua Lua, x: f64, y: f64) -> rlua::Result<Option<Drawin<'lua>>> {
+    let drawins = lua.named_registry_value::<Vec<AnyUserData>>(DRAWINS_HANDLE)?;
+    for drawin in drawins.into_iter().rev() {
+        let drawin = Drawin::cast(drawin.into())?;
+        let Area { origin: Origin { x: drawin_x, y: drawin_y },
+                   size: Size { width, height } } = drawin.get_geometry()?;
+        if x >= drawin_x as f64 && x < (drawin_x + width) as f64 && y >= drawin_y as f64
+           && y < (drawin_y + height) as f64
+        {
+            return Ok(Some(drawin))
+        }
+    }
+    Ok(None)
+}
+
+/// Gets where the visible drawins are and how opaque, from bottom to top,
+/// along with the ids of the drawables they draw in.
+pub fn drawin_contents() -> Vec<(Area, f32, usize)> {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 match visible_contents(&*lua) {
+                     Ok(contents) => contents,
+                     Err(err) => {
+                         warn!("Could not get the contents of drawins: {}", err);
+                         vec![]
+                     }
+                 }
+             })
+}
+
+fn visible_contents(lua: &Lua) -> rlua::Result<Vec<(Area, f32, usize)>> {
+    let drawins = lua.named_registry_value::<Vec<AnyUserData>>(DRAWINS_HANDLE)?;
+    let mut contents = Vec::with_capacity(drawins.len());
+    for drawin in drawins {
+        let drawin = Drawin::cast(drawin.into())?;
+        let state = drawin.state()?;
+        let drawable = drawin.0.table()?.get::<_, AnyUserData>("drawable")?;
+        let id = Drawable::cast(drawable.into())?.id()?;
+        contents.push((state.geometry, state.opacity as f32, id));
+    }
+    Ok(contents)
+}
+
+/// Determines if there is a visible drawin at the given layout coordinates.
+///
+/// This is false if Lua has not been set up yet.
+pub fn over_drawin(x: f64, y: f64) -> bool {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 drawin_at(&*lua, x, y).map(|drawin| drawin.is_some())
+                                       .unwrap_or(false)
+             })
+}
+
+/// Emit a button press or release on the drawin at the given layout
+/// coordinates.
+///
+/// The coordinates given to Lua are relative to the drawin. Returns false if
+/// there is no drawin there.
+pub fn drawin_button(x: f64, y: f64, button: u32, pressed: bool, mods: u32) -> bool {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 match emit_button(&*lua, x, y, button, pressed, mods) {
+                     Ok(over_drawin) => over_drawin,
+                     Err(err) => {
+                         warn!("Could not send button to drawin: {}", err);
+                         true
+                     }
+                 }
+             })
+}
+
+fn emit_button(lua: &Lua,
+               x: f64,
+               y: f64,
+               button: u32,
+               pressed: bool,
+               mods: u32)
+               -> rlua::Result<bool> {
+    let drawin = match drawin_at(lua, x, y)? {
+        Some(drawin) => drawin,
+        None => return Ok(false)
+    };
+    let Origin { x: drawin_x, y: drawin_y } = drawin.get_geometry()?.origin;
+    let name = if pressed { "button::press" } else { "button::release" };
+    let args = (x as i32 - drawin_x,
+                y as i32 - drawin_y,
+                ::lua::button_to_lua(button),
+                ::lua::mods_to_lua(lua, mods)?);
+    signal::emit_object_signal(lua, drawin.0, name.into(), args)?;
+    Ok(true)
+}
@@ -0,0 +1,380 @@
+//! The input devices of the compositor and their libinput settings.
+//!
+//! Absolute devices, like tablets and touchscreens, can also be mapped to
+//! an output by setting `output` to the name of the output.
+
+use super::class::{self, Class, ClassBuilder};
+use super::object::{self, Object, Objectable};
+use super::property::Property;
+use super::signal;
+use awesome::LUA;
+use compositor::{self, AccelProfile, InputConfig, InputMatch, InputRule, InputType, ScrollMethod};
+use rlua::{self, AnyUserData, Error::RuntimeError, Integer, Lua, Table, ToLua, UserData,
+           UserDataMethods, Value};
+use std::default::Default;
+use std::fmt::{self, Display, Formatter};
+
+/// The input devices that are plugged in, in the order they were added.
+pub const INPUTS_HANDLE: &'static str = "__inputs";
+
+/// The libinput settings that can be set on an input.
+const SETTINGS: [&'static str; 9] = ["tap_to_click",
+                                     "natural_scrolling",
+                                     "accel_profile",
+                                     "accel_speed",
+                                     "left_handed",
+                                     "disable_while_typing",
+                                     "scroll_method",
+                                     "middle_emulation",
+                                     "output"];
+
+#[derive(Clone, Debug)]
+pub struct InputState {
+    /// The id of the device, which is unique unlike its name.
+    id: usize,
+    name: String,
+    input_type: InputType,
+    config: InputConfig
+}
+
+pub struct Input<'lua>(Object<'lua>);
+
+impl Default for InputState {
+    fn default() -> Self {
+        InputState { id: 0,
+                     name: String::new(),
+                     input_type: InputType::Pointer,
+                     config: InputConfig::default() }
+    }
+}
+
+impl<'lua> Input<'lua> {
+    fn new(lua: &'lua Lua,
+           id: usize,
+           name: String,
+           input_type: InputType,
+           config: InputConfig)
+           -> rlua::Result<Object<'lua>> {
+        let class = class::class_setup(lua, "input")?;
+        let mut input = Input::cast(Input::allocate(lua, class)?.build())?;
+        {
+            let mut state = input.get_object_mut()?;
+            state.id = id;
+            state.name = name;
+            state.input_type = input_type;
+            state.config = config;
+        }
+        Ok(input.0)
+    }
+}
+
+impl Display for InputState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Input: {:p}", self)
+    }
+}
+
+impl<'lua> ToLua<'lua> for Input<'lua> {
+    fn to_lua(self, lua: &'lua Lua) -> rlua::Result<Value<'lua>> {
+        self.0.to_lua(lua)
+    }
+}
+
+impl UserData for InputState {
+    fn add_methods(methods: &mut UserDataMethods<Self>) {
+        object::default_add_methods(methods);
+    }
+}
+
+pub fn init(lua: &Lua) -> rlua::Result<Class> {
+    lua.set_named_registry_value(INPUTS_HANDLE, lua.create_table()?)?;
+    property_setup(lua, method_setup(lua, Class::builder(lua, "input", None)?)?)?
+        .save_class("input")?
+        .build()
+}
+
+fn method_setup<'lua>(lua: &'lua Lua,
+                      builder: ClassBuilder<'lua>)
+                      -> rlua::Result<ClassBuilder<'lua>> {
+    builder.method("get".into(), lua.create_function(get_inputs)?)?
+           .method("configure".into(), lua.create_function(configure)?)
+}
+
+fn property_setup<'lua>(lua: &'lua Lua,
+                        builder: ClassBuilder<'lua>)
+                        -> rlua::Result<ClassBuilder<'lua>> {
+    let mut builder = builder.property(Property::new("name".into(),
+                                                     None,
+                                                     Some(lua.create_function(get_name)?),
+                                                     None))?
+                             .property(Property::new("type".into(),
+                                                     None,
+                                                     Some(lua.create_function(get_type)?),
+                                                     None))?;
+    for &setting in SETTINGS.iter() {
+        let get = lua.create_function(move |lua, obj: AnyUserData| {
+                                          get_setting(lua, obj, setting)
+                                      })?;
+        let set = lua.create_function(move |lua, (obj, val): (AnyUserData, Value)| {
+                                          set_setting(lua, obj, setting, val)
+                                      })?;
+        builder = builder.property(Property::new(setting.into(), None, Some(get), Some(set)))?;
+    }
+    Ok(builder)
+}
+
+impl_objectable!(Input, InputState);
+
+fn get_name<'lua>(lua: &'lua Lua, obj: AnyUserData<'lua>) -> rlua::Result<Value<'lua>> {
+    obj.borrow::<InputState>()?.name.clone().to_lua(lua)
+}
+
+fn get_type<'lua>(lua: &'lua Lua, obj: AnyUserData<'lua>) -> rlua::Result<Value<'lua>> {
+    obj.borrow::<InputState>()?.input_type.as_str().to_lua(lua)
+}
+
+fn get_setting<'lua>(lua: &'lua Lua,
+                     obj: AnyUserData<'lua>,
+                     setting: &str)
+                     -> rlua::Result<Value<'lua>> {
+    let config = obj.borrow::<InputState>()?.config.clone();
+    match setting {
+        "tap_to_click" => config.tap_to_click.to_lua(lua),
+        "natural_scrolling" => config.natural_scrolling.to_lua(lua),
+        "accel_profile" => config.accel_profile.map(|profile| profile.as_str()).to_lua(lua),
+        "accel_speed" => config.accel_speed.to_lua(lua),
+        "left_handed" => config.left_handed.to_lua(lua),
+        "disable_while_typing" => config.disable_while_typing.to_lua(lua),
+        "scroll_method" => config.scroll_method.map(|method| method.as_str()).to_lua(lua),
+        "middle_emulation" => config.middle_emulation.to_lua(lua),
+        "output" => config.output.to_lua(lua),
+        _ => Ok(Value::Nil)
+    }
+}
+
+/// Set a setting on the input, and every other input with the same name.
+fn set_setting<'lua>(lua: &'lua Lua,
+                     obj: AnyUserData<'lua>,
+                     setting: &str,
+                     val: Value<'lua>)
+                     -> rlua::Result<()> {
+    let name = obj.borrow::<InputState>()?.name.clone();
+    let mut config = InputConfig::default();
+    parse_setting(&mut config, setting, val)?;
+    add_rule(lua,
+             InputRule { matches: InputMatch::Name(name),
+                         config })
+}
+
+fn parse_setting(config: &mut InputConfig, setting: &str, val: Value) -> rlua::Result<()> {
+    fn as_bool(setting: &str, val: Value) -> rlua::Result<Option<bool>> {
+        match val {
+            Value::Nil => Ok(None),
+            Value::Boolean(val) => Ok(Some(val)),
+            _ => Err(RuntimeError(format!("{} must be a boolean", setting)))
+        }
+    }
+    fn as_str(setting: &str, val: Value) -> rlua::Result<Option<String>> {
+        match val {
+            Value::Nil => Ok(None),
+            Value::String(val) => Ok(Some(val.to_str()?.into())),
+            _ => Err(RuntimeError(format!("{} must be a string", setting)))
+        }
+    }
+    match setting {
+        "tap_to_click" => config.tap_to_click = as_bool(setting, val)?,
+        "natural_scrolling" => config.natural_scrolling = as_bool(setting, val)?,
+        "accel_profile" => {
+            config.accel_profile = match as_str(setting, val)? {
+                None => None,
+                Some(profile) => {
+                    let err = format!("{} is not a valid accel profile", profile);
+                    Some(AccelProfile::from_str(&profile).ok_or(RuntimeError(err))?)
+                }
+            }
+        }
+        "accel_speed" => {
+            config.accel_speed = match val {
+                Value::Nil => None,
+                Value::Integer(speed) => Some(speed as f64),
+                Value::Number(speed) => Some(speed),
+                _ => return Err(RuntimeError("accel_speed must be a number".into()))
+            }
+        }
+        "left_handed" => config.left_handed = as_bool(setting, val)?,
+        "disable_while_typing" => config.disable_while_typing = as_bool(setting, val)?,
+        "scroll_method" => {
+            config.scroll_method = match as_str(setting, val)? {
+                None => None,
+                Some(method) => {
+                    let err = format!("{} is not a valid scroll method", method);
+                    Some(ScrollMethod::from_str(&method).ok_or(RuntimeError(err))?)
+                }
+            }
+        }
+        "middle_emulation" => config.middle_emulation = as_bool(setting, val)?,
+        "output" => config.output = as_str(setting, val)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Configure the inputs that match the table, now and when they are plugged
+/// in.
+///
+/// The inputs can be matched by `name` or `type`. If neither is given then
+/// every input is configured.
+fn configure(lua: &Lua, rule: Table) -> rlua::Result<()> {
+    let name = rule.get::<_, Option<String>>("name")?;
+    let input_type = rule.get::<_, Option<String>>("type")?;
+    let matches = match (name, input_type) {
+        (Some(name), _) => InputMatch::Name(name),
+        (None, Some(input_type)) => {
+            InputMatch::Type(InputType::from_str(&input_type)
+                .ok_or(RuntimeError(format!("{} is not a valid input type", input_type)))?)
+        }
+        (None, None) => InputMatch::All
+    };
+    let mut config = InputConfig::default();
+    for &setting in SETTINGS.iter() {
+        parse_setting(&mut config, setting, rule.get(setting)?)?;
+    }
+    add_rule(lua, InputRule { matches, config })
+}
+
+/// Update the inputs the rule matches and send it to the compositor.
+fn add_rule(lua: &Lua, rule: InputRule) -> rlua::Result<()> {
+    let inputs = lua.named_registry_value::<Vec<AnyUserData>>(INPUTS_HANDLE)?;
+    for input in inputs {
+        let matched = {
+            let mut state = input.borrow_mut::<InputState>()?;
+            let matched = rule.matches(&state.name, state.input_type);
+            if matched {
+                state.config.merge(&rule.config);
+            }
+            matched
+        };
+        if matched {
+            signal::emit_object_signal(lua, input.into(), "property::config".into(), ())?;
+        }
+    }
+    compositor::configure_input(rule);
+    Ok(())
+}
+
+fn get_inputs<'lua>(lua: &'lua Lua, _: Value<'lua>) -> rlua::Result<Table<'lua>> {
+    lua.named_registry_value::<Table>(INPUTS_HANDLE)
+}
+
+/// Creates an input for a device that was just plugged in.
+pub fn add_input(id: usize, name: String, input_type: InputType, config: InputConfig) {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 if let Err(err) = add(&*lua, id, name.clone(), input_type, config) {
+                     warn!("Could not add input {}: {}", name, err);
+                 }
+             })
+}
+
+/// Removes the input of a device that was unplugged.
+pub fn remove_input(id: usize, input_type: InputType) {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 if let Err(err) = remove(&*lua, id, input_type) {
+                     warn!("Could not remove input {}: {}", id, err);
+                 }
+             })
+}
+
+fn add(lua: &Lua,
+       id: usize,
+       name: String,
+       input_type: InputType,
+       config: InputConfig)
+       -> rlua::Result<()> {
+    let input = Input::new(lua, id, name, input_type, config)?;
+    let inputs = lua.named_registry_value::<Table>(INPUTS_HANDLE)?;
+    let index = inputs.len()? + 1;
+    inputs.set(index, input)
+}
+
+fn remove(lua: &Lua, id: usize, input_type: InputType) -> rlua::Result<()> {
+    let inputs = lua.named_registry_value::<Table>(INPUTS_HANDLE)?;
+    let inputs_count = inputs.len()?;
+    for pair in inputs.clone().pairs::<Integer, AnyUserData>() {
+        let (key, value) = pair?;
+        let matches = {
+            let state = value.borrow::<InputState>()?;
+            state.id == id && state.input_type == input_type
+        };
+        if matches {
+            // Now remove this by shifting everything down...
+            for index in key..inputs_count {
+                inputs.set(index, inputs.get::<_, Value>(index + 1)?)?;
+            }
+            inputs.set(inputs_count, Value::Nil)?;
+            break
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::input;
+    use compositor::{InputConfig, InputType};
+    use rlua::{Lua, Value};
+
+    #[test]
+    fn input_get_lists_inputs() {
+        let lua = Lua::new();
+        input::init(&lua).unwrap();
+        let config = InputConfig::default();
+        input::add(&lua, 1, "touchpad".into(), InputType::Touchpad, config.clone()).unwrap();
+        input::add(&lua, 2, "mouse".into(), InputType::Pointer, config).unwrap();
+        input::remove(&lua, 1, InputType::Touchpad).unwrap();
+        lua.eval(
+            r#"
+local inputs = input.get()
+assert(#inputs == 1)
+assert(inputs[1].name == "mouse")
+assert(inputs[1].type == "pointer")
+assert(inputs[1].tap_to_click == nil)
+"#,
+            None
+        ).unwrap()
+    }
+
+    #[test]
+    fn input_remove_same_name() {
+        let lua = Lua::new();
+        input::init(&lua).unwrap();
+        let config = InputConfig::default();
+        input::add(&lua, 1, "combo".into(), InputType::Keyboard, config.clone()).unwrap();
+        input::add(&lua, 2, "combo".into(), InputType::Touchpad, config.clone()).unwrap();
+        input::add(&lua, 3, "combo".into(), InputType::Keyboard, config).unwrap();
+        input::remove(&lua, 3, InputType::Keyboard).unwrap();
+        input::remove(&lua, 2, InputType::Keyboard).unwrap();
+        lua.eval(
+            r#"
+local inputs = input.get()
+assert(#inputs == 2)
+assert(inputs[1].type == "keyboard")
+assert(inputs[2].type == "touchpad")
+"#,
+            None
+        ).unwrap()
+    }
+
+    #[test]
+    fn input_bool_setting_must_be_boolean() {
+        let lua = Lua::new();
+        let mut config = InputConfig::default();
+        input::parse_setting(&mut config, "tap_to_click", Value::Boolean(false)).unwrap();
+        assert_eq!(config.tap_to_click, Some(false));
+        let string = Value::String(lua.create_string("false").unwrap());
+        assert!(input::parse_setting(&mut config, "tap_to_click", string).is_err());
+        assert!(input::parse_setting(&mut config, "left_handed", Value::Integer(0)).is_err());
+        assert_eq!(config.tap_to_click, Some(false))
+    }
+}
@@ -2,21 +2,27 @@
 
 use super::class::{self, Class, ClassBuilder};
 use super::object::{self, Object, Objectable};
-use rlua::{self, Lua, Table, ToLua, UserData, UserDataMethods, Value};
+use super::property::Property;
+use super::signal;
+use rlua::{self, AnyUserData, Lua, Table, ToLua, UserData, UserDataMethods, Value};
 use std::default::Default;
 use std::fmt::{self, Display, Formatter};
+use wlroots::xkbcommon::xkb::{self, keysym_from_name, keysym_get_name, KEY_NoSymbol,
+                              KEYSYM_NO_FLAGS};
 
 #[derive(Clone, Debug)]
 pub struct KeyState {
-    // TODO Fill in
-    dummy: i32
+    keysym: xkb::Keysym,
+    /// The mask of modifiers that have to be held for this key.
+    modifiers: u32
 }
 
 pub struct Key<'lua>(Object<'lua>);
 
 impl Default for KeyState {
     fn default() -> Self {
-        KeyState { dummy: 0 }
+        KeyState { keysym: KEY_NoSymbol,
+                   modifiers: 0 }
     }
 }
 
@@ -27,6 +33,33 @@ impl<'lua> Key<'lua> {
         Ok(Key::allocate(lua, class)?.handle_constructor_argument(args)?
                                      .build())
     }
+
+    pub fn keysym(&self) -> rlua::Result<xkb::Keysym> {
+        Ok(self.state()?.keysym)
+    }
+
+    pub fn set_keysym(&mut self, keysym: xkb::Keysym) -> rlua::Result<()> {
+        self.get_object_mut()?.keysym = keysym;
+        Ok(())
+    }
+
+    pub fn modifiers(&self) -> rlua::Result<u32> {
+        Ok(self.state()?.modifiers)
+    }
+
+    pub fn set_modifiers(&mut self, mods: Table<'lua>) -> rlua::Result<()> {
+        use lua::mods_to_rust;
+        self.get_object_mut()?.modifiers = mods_to_rust(mods)?;
+        Ok(())
+    }
+
+    /// Determine if this key binding is triggered by pressing `keysym` while
+    /// the modifiers in `mods` are active.
+    pub fn matches(&self, keysym: xkb::Keysym, mods: u32) -> rlua::Result<bool> {
+        use lua::mods_match;
+        let state = self.state()?;
+        Ok(state.keysym == keysym && mods_match(state.modifiers, mods))
+    }
 }
 
 impl Display for KeyState {
@@ -63,11 +96,54 @@ fn method_setup<'lua>(lua: &'lua Lua,
 fn property_setup<'lua>(lua: &'lua Lua,
                         builder: ClassBuilder<'lua>)
                         -> rlua::Result<ClassBuilder<'lua>> {
-    // TODO Do properly
-    builder.dummy_property("version".into(), "0".to_lua(lua)?)?
-           .dummy_property("themes_path".into(),
-                           "/usr/share/awesome/themes".to_lua(lua)?)?
-           .dummy_property("conffile".into(), "".to_lua(lua)?)
+    builder.property(Property::new("key".into(),
+                                   Some(lua.create_function(set_key)?),
+                                   Some(lua.create_function(get_keysym)?),
+                                   Some(lua.create_function(set_key)?)))?
+           .property(Property::new("keysym".into(),
+                                   None,
+                                   Some(lua.create_function(get_keysym)?),
+                                   None))?
+           .property(Property::new("modifiers".into(),
+                                   Some(lua.create_function(set_modifiers)?),
+                                   Some(lua.create_function(get_modifiers)?),
+                                   Some(lua.create_function(set_modifiers)?)))
 }
 
 impl_objectable!(Key, KeyState);
+
+fn set_key<'lua>(lua: &'lua Lua,
+                 (obj, name): (AnyUserData<'lua>, String))
+                 -> rlua::Result<Value<'lua>> {
+    let mut key = Key::cast(obj.clone().into())?;
+    let keysym = keysym_from_name(name.as_str(), KEYSYM_NO_FLAGS);
+    if keysym == KEY_NoSymbol {
+        warn!("There is no keysym named {}", name);
+    }
+    key.set_keysym(keysym)?;
+    signal::emit_object_signal(lua, obj.clone().into(), "property::key".into(), name.clone())?;
+    signal::emit_object_signal(lua, obj.into(), "property::keysym".into(), name)?;
+    Ok(Value::Nil)
+}
+
+fn get_keysym<'lua>(lua: &'lua Lua, obj: AnyUserData<'lua>) -> rlua::Result<Value<'lua>> {
+    let keysym = Key::cast(obj.into())?.keysym()?;
+    if keysym == KEY_NoSymbol {
+        return Ok(Value::Nil)
+    }
+    keysym_get_name(keysym).to_lua(lua)
+}
+
+fn set_modifiers<'lua>(lua: &'lua Lua,
+                       (obj, modifiers): (AnyUserData<'lua>, Table<'lua>))
+                       -> rlua::Result<Value<'lua>> {
+    let mut key = Key::cast(obj.clone().into())?;
+    key.set_modifiers(modifiers.clone())?;
+    signal::emit_object_signal(lua, obj.into(), "property::modifiers".into(), modifiers)?;
+    Ok(Value::Nil)
+}
+
+fn get_modifiers<'lua>(lua: &'lua Lua, obj: AnyUserData<'lua>) -> rlua::Result<Value<'lua>> {
+    use lua::mods_to_lua;
+    mods_to_lua(lua, Key::cast(obj.into())?.modifiers()?).map(Value::Table)
+}
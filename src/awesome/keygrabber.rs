@@ -26,7 +26,7 @@ pub fn init(lua: &Lua) -> rlua::Result<()> {
 #[allow(deprecated)]
 /// Given the current input, handle calling the Lua defined callback if it is
 /// defined with the input.
-pub fn keygrabber_handle(mods: Vec<Key>, sym: Key, state: wlr_key_state) -> rlua::Result<()> {
+pub fn keygrabber_handle(mods: u32, sym: Key, state: wlr_key_state) -> rlua::Result<()> {
     LUA.with(|lua| {
                  let lua = lua.borrow_mut();
                  let lua_state = if state == wlr_key_state::WLR_KEY_PRESSED {
@@ -35,7 +35,7 @@ pub fn keygrabber_handle(mods: Vec<Key>, sym: Key, state: wlr_key_state) -> rlua
                                      "release"
                                  }.into();
                  let lua_sym = keysym_get_name(sym);
-                 let lua_mods = ::lua::mods_to_lua(&*lua, &mods)?;
+                 let lua_mods = ::lua::mods_to_lua(&*lua, mods)?;
                  let res = call_keygrabber(&*lua, (lua_mods, lua_sym, lua_state));
                  match res {
                      Ok(_) | Err(rlua::Error::FromLuaConversionError { .. }) => Ok(()),
@@ -44,6 +44,11 @@ pub fn keygrabber_handle(mods: Vec<Key>, sym: Key, state: wlr_key_state) -> rlua
              })
 }
 
+/// Whether a Lua callback is currently grabbing the keyboard.
+pub fn keygrabber_running() -> bool {
+    LUA.with(|lua| isrunning(&*lua.borrow(), ()).unwrap_or(false))
+}
+
 /// Call the Lua callback function for when a key is pressed.
 fn call_keygrabber(lua: &Lua, (mods, key, event): (Table, String, String)) -> rlua::Result<()> {
     let lua_callback = lua.named_registry_value::<Function>(KEYGRABBER_CALLBACK)?;
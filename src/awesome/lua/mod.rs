@@ -6,7 +6,8 @@ mod init_path;
 mod utils;
 
 pub use self::types::{LuaQuery, LuaResponse};
-pub use self::utils::{mods_to_lua, mods_to_rust, mouse_events_to_lua};
+pub use self::utils::{button_to_lua, color_to_rust, mods_match, mods_to_lua, mods_to_rust,
+                      pressed_buttons_to_lua, surface_to_lua};
 
 use glib::MainLoop;
 use rlua;
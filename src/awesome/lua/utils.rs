@@ -1,65 +1,107 @@
 //! Utilities to talk to Lua
 
-use wlroots::{events::{key_events::Key,
-                       pointer_events::{wlr_button_state, BTN_EXTRA, BTN_LEFT, BTN_MIDDLE,
-                                        BTN_RIGHT, BTN_SIDE}},
-              xkbcommon::xkb::keysyms::*};
+use wlroots::events::pointer_events::{BTN_EXTRA, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT, BTN_SIDE};
 
-use rlua::{self, Error::RuntimeError, Lua, Table, Value};
+use awesome::MODIFIERS;
+use cairo::ImageSurface;
+use glib::translate::ToGlibPtr;
+use rlua::{self, Error::RuntimeError, LightUserData, Lua, Table, UserData, UserDataMethods,
+           Value};
 
-/// Human readable versions of the standard modifier keys.
-const MOD_NAMES: [&str; 8] = ["Shift", "Caps", "Control", "Alt", "Mod2", "Mod3", "Mod4", "Mod5"];
 /// Keycodes corresponding to various button events.
 const MOUSE_EVENTS: [u32; 5] = [BTN_LEFT, BTN_RIGHT, BTN_MIDDLE, BTN_SIDE, BTN_EXTRA];
 
-/// Convert a modifier to the Lua interpretation
-pub fn mods_to_lua<'lua>(lua: &'lua Lua, mods: &[Key]) -> rlua::Result<Table<'lua>> {
-    let mut mods_list: Vec<String> = Vec::with_capacity(MOD_NAMES.len());
-    for modifier in mods {
-        mods_list.push(match *modifier {
-                           KEY_Shift_L | KEY_Shift_R => "Shift",
-                           KEY_Control_L | KEY_Control_R => "Control",
-                           KEY_Caps_Lock => "Caps",
-                           KEY_Alt_L | KEY_Alt_R => "Alt",
-                           KEY_Meta_L | KEY_Meta_R => "Mod2",
-                           KEY_Super_L | KEY_Super_R => "Mod4",
-                           _ => continue
-                       }.into());
-    }
-    lua.create_table_from(mods_list.into_iter().enumerate())
+/// Convert a modifier mask to the Lua interpretation
+pub fn mods_to_lua<'lua>(lua: &'lua Lua, mods: u32) -> rlua::Result<Table<'lua>> {
+    let names = MODIFIERS.lock().expect("Modifiers was poisoned").names(mods);
+    lua.create_table_from(names.into_iter().enumerate().map(|(index, name)| (index + 1, name)))
 }
 
 /// Convert a modifier to the Rust interpretation, from the Lua interpretation
-pub fn mods_to_rust(mods_table: Table) -> rlua::Result<Vec<Key>> {
-    let mut mods = Vec::with_capacity(MOD_NAMES.len());
+///
+/// Both the real modifiers (e.g "Mod4") and the virtual modifiers of the
+/// keymap (e.g "Super") are understood.
+pub fn mods_to_rust(mods_table: Table) -> rlua::Result<u32> {
+    let modifiers = MODIFIERS.lock().expect("Modifiers was poisoned");
+    let mut mods = 0;
     for modifier in mods_table.pairs::<Value, String>() {
-        mods.push(match &*modifier?.1 {
-                      "Shift" => KEY_Shift_L,
-                      "Caps" | "Lock" => KEY_Caps_Lock,
-                      "Control" | "Ctrl" => KEY_Control_L,
-                      "Alt" | "Mod1" => KEY_Alt_L,
-                      "Mod2" => KEY_Meta_L,
-                      "Mod3" => KEY_Alt_L,
-                      "Mod4" => KEY_Super_L,
-                      "Mod5" => KEY_Hyper_L,
-                      string => {
-                          return Err(RuntimeError(format!("{} is an invalid modifier", string)))?
-                      }
-                  })
+        let modifier = modifier?.1;
+        match modifiers.mask(&modifier) {
+            Some(mask) => mods |= mask,
+            None => return Err(RuntimeError(format!("{} is an invalid modifier", modifier)))
+        }
     }
     Ok(mods)
 }
 
-/// Convert a mouse event from Wayland to the representation Lua expcets
-pub fn mouse_events_to_lua(_: &rlua::Lua,
-                           button: u32,
-                           button_state: wlr_button_state)
-                           -> rlua::Result<Vec<bool>> {
-    let mut event_list = Vec::with_capacity(MOUSE_EVENTS.len());
-    for mouse_event in &MOUSE_EVENTS[..5] {
-        let state_pressed = button_state == wlr_button_state::WLR_BUTTON_PRESSED;
-        let is_pressed = button == *mouse_event && state_pressed;
-        event_list.push(is_pressed);
+/// Determine if the modifiers of a binding match the active modifiers.
+///
+/// The lock modifiers (Caps Lock and Num Lock) are ignored.
+pub fn mods_match(binding: u32, active: u32) -> bool {
+    MODIFIERS.lock()
+             .expect("Modifiers was poisoned")
+             .matches(binding, active)
+}
+
+/// Convert a button code from Wayland to the X button number Lua expects
+pub fn button_to_lua(button: u32) -> u32 {
+    match button {
+        BTN_LEFT => 1,
+        BTN_MIDDLE => 2,
+        BTN_RIGHT => 3,
+        BTN_SIDE => 8,
+        BTN_EXTRA => 9,
+        // Other buttons don't have a standard number, so keep them distinct.
+        button => button
+    }
+}
+
+/// Convert a color from Lua, e.g "#ff0000" or "#ff000080", to RGBA.
+///
+/// Colors without alpha are opaque.
+pub fn color_to_rust(color: &str) -> rlua::Result<[u8; 4]> {
+    let invalid = || RuntimeError(format!("{} is an invalid color", color));
+    let hex = match (color.starts_with('#'), color.len()) {
+        (true, 7) | (true, 9) => &color[1..],
+        _ => return Err(invalid())
+    };
+    let mut rgba = [0xff; 4];
+    for (index, channel) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+        let digits = hex.get(index * 2..index * 2 + 2).ok_or_else(invalid)?;
+        *channel = u8::from_str_radix(digits, 16).map_err(|_| invalid())?;
     }
-    Ok(event_list)
+    Ok(rgba)
+}
+
+/// A cairo surface owned by Lua.
+///
+/// Its `__gc` drops the surface, which destroys it with
+/// `cairo_surface_destroy`.
+pub struct LuaSurface(ImageSurface);
+
+impl UserData for LuaSurface {
+    fn add_methods(methods: &mut UserDataMethods<Self>) {
+        // LGI has to take its own reference to the surface, e.g with
+        // `cairo.Surface(surface:pointer(), false)`.
+        methods.add_method("pointer", |_, surface, ()| {
+            Ok(LightUserData(surface.0.to_glib_none().0 as _))
+        });
+    }
+}
+
+/// Give a cairo surface to Lua, which destroys it when it's collected.
+pub fn surface_to_lua<'lua>(lua: &'lua Lua,
+                            surface: Option<ImageSurface>)
+                            -> rlua::Result<Value<'lua>> {
+    match surface {
+        None => Ok(Value::Nil),
+        Some(surface) => lua.create_userdata(LuaSurface(surface)).map(Value::UserData)
+    }
+}
+
+/// Convert the buttons that are held down to the representation Lua expects
+pub fn pressed_buttons_to_lua(buttons: &[u32]) -> Vec<bool> {
+    MOUSE_EVENTS.iter()
+                .map(|mouse_event| buttons.contains(mouse_event))
+                .collect()
 }
@@ -14,6 +14,7 @@ mod button;
 mod tag;
 mod key;
 mod drawin;
+mod input;
 mod drawable;
 mod mouse;
 mod root;
@@ -22,14 +23,23 @@ mod object;
 mod class;
 mod property;
 mod xproperty;
+mod selection;
 
 pub use self::lua::LUA;
 
-pub use self::keygrabber::keygrabber_handle;
-pub use self::mousegrabber::mousegrabber_handle;
+pub use self::awesome::{content_changed, gesture_pinch, gesture_swipe, idle_start, idle_stop,
+                        selection_changed, xkb_group_changed, xkb_map_changed};
+pub use self::client::{client_requests_no_titlebar, manage_client, titlebar_button,
+                       titlebar_mouse, titlebar_resized, unmanage_client};
+pub use self::drawin::{drawin_button, drawin_contents, over_drawin};
+pub use self::input::{add_input, remove_input};
+pub use self::keygrabber::{keygrabber_handle, keygrabber_running};
+pub use self::mousegrabber::{mousegrabber_handle, mousegrabber_running};
 pub use self::object::Object;
+pub use self::root::{root_button, root_key};
 
-use ipc::{Output, Pointer};
+use compositor::ContentSource;
+use ipc::{Content, ModifierMap, Output, Pointer, Selection, Xkb};
 
 pub const GLOBAL_SIGNALS: &'static str = "__awesome_global_signals";
 pub const XCB_CONNECTION_HANDLE: &'static str = "__xcb_connection";
@@ -37,6 +47,10 @@ pub const XCB_CONNECTION_HANDLE: &'static str = "__xcb_connection";
 lazy_static! {
     pub static ref OUTPUTS: Mutex<Vec<Output>> = Mutex::new(vec![]);
     pub static ref POINTER: Mutex<Pointer> = Mutex::new(Pointer::default());
+    pub static ref MODIFIERS: Mutex<ModifierMap> = Mutex::new(ModifierMap::default());
+    pub static ref XKB: Mutex<Xkb> = Mutex::new(Xkb::default());
+    pub static ref SELECTION: Mutex<Selection> = Mutex::new(Selection::default());
+    pub static ref CONTENTS: Mutex<Vec<(ContentSource, Content)>> = Mutex::new(vec![]);
 }
 
 pub fn init(lua: &Lua) -> rlua::Result<()> {
@@ -53,8 +67,10 @@ pub fn init(lua: &Lua) -> rlua::Result<()> {
     mouse::init(lua)?;
     tag::init(lua)?;
     drawin::init(lua)?;
+    input::init(lua)?;
     drawable::init(lua)?;
     mousegrabber::init(lua)?;
+    selection::init(lua)?;
     Ok(())
 }
 
@@ -1,9 +1,16 @@
 //! TODO Fill in
 
-use awesome::{OUTPUTS, POINTER};
-use rlua::{self, AnyUserData, Lua, MetaMethod, Table, ToLua, UserData, UserDataMethods, Value};
+use super::client::client_by_view;
+use super::drawin::drawin_at;
+use super::object::Objectable;
+use super::screen::{Screen, SCREENS_HANDLE};
+use super::signal;
+use awesome::POINTER;
+use rlua::{self, AnyUserData, Function, Lua, MetaMethod, Table, ToLua, UserData, UserDataMethods,
+           Value};
 use std::default::Default;
 use std::fmt::{self, Display, Formatter};
+use wlroots::{Area, Origin, Size};
 
 const INDEX_MISS_FUNCTION: &'static str = "__index_miss_function";
 const NEWINDEX_MISS_FUNCTION: &'static str = "__newindex_miss_function";
@@ -29,6 +36,7 @@ impl Display for MouseState {
 impl UserData for MouseState {
     fn add_methods(methods: &mut UserDataMethods<Self>) {
         methods.add_meta_function(MetaMethod::Index, index);
+        methods.add_meta_function(MetaMethod::NewIndex, newindex);
     }
 }
 
@@ -45,6 +53,8 @@ pub fn init(lua: &Lua) -> rlua::Result<()> {
 
 fn method_setup(lua: &Lua, mouse_table: &Table) -> rlua::Result<()> {
     mouse_table.set("coords", lua.create_function(coords)?)?;
+    mouse_table.set("object_under_pointer",
+                     lua.create_function(object_under_pointer)?)?;
     mouse_table.set("set_index_miss_handler",
                      lua.create_function(set_index_miss)?)?;
     mouse_table.set("set_newindex_miss_handler",
@@ -52,73 +62,266 @@ fn method_setup(lua: &Lua, mouse_table: &Table) -> rlua::Result<()> {
     Ok(())
 }
 
+/// Gets the position of the pointer and the buttons that are held down.
+///
+/// If a table with coordinates is given, the pointer is moved there and that
+/// position is returned. Missing coordinates in the table default to the
+/// current ones.
 fn coords<'lua>(lua: &'lua Lua,
-                (coords, _ignore_enter): (rlua::Value<'lua>, rlua::Value<'lua>))
+                (coords, ignore_enter): (Value<'lua>, Option<bool>))
                 -> rlua::Result<Table<'lua>> {
+    use lua::pressed_buttons_to_lua;
     let mut pointer = POINTER.lock().expect("Lock was poisoned");
-    match coords {
-        rlua::Value::Table(coords) => {
-            let (x, y): (i32, i32) = (coords.get("x")?, coords.get("y")?);
-            // TODO The ignore_enter is supposed to not send a send event to
-            // the client That's not
-            // possible, at least until wlroots is complete.
-            pointer.set_position((x as _, y as _));
-            Ok(coords)
-        }
-        _ => {
-            // get the coords
-            let coords = lua.create_table()?;
-            let (x, y) = pointer.position;
-            coords.set("x", x as i32)?;
-            coords.set("y", y as i32)?;
-            // TODO It expects a table of what buttons were pressed.
-            coords.set("buttons", lua.create_table()?)?;
-            Ok(coords)
+    if let Value::Table(coords) = coords {
+        let (x, y) = pointer.position;
+        let x = coords.get::<_, Option<f64>>("x")?.unwrap_or(x);
+        let y = coords.get::<_, Option<f64>>("y")?.unwrap_or(y);
+        if !x.is_finite() || !y.is_finite() {
+            return Err(rlua::Error::RuntimeError(format!("{}, {} is not a position", x, y)))
         }
+        pointer.set_position((x, y), ignore_enter.unwrap_or(false));
     }
+    let coords = lua.create_table()?;
+    let (x, y) = pointer.position;
+    coords.set("x", x as i32)?;
+    coords.set("y", y as i32)?;
+    coords.set("buttons", pressed_buttons_to_lua(&pointer.buttons))?;
+    Ok(coords)
+}
+
+/// Gets the meta table of the mouse object, which is where the miss handlers
+/// are stored so that they can't be indexed.
+fn mouse_meta(lua: &Lua) -> rlua::Result<Table> {
+    let mouse = lua.globals().get::<_, AnyUserData>("mouse")?;
+    table_meta(&mouse.get_user_value::<Table>()?)
 }
 
-fn set_index_miss(lua: &Lua, func: rlua::Function) -> rlua::Result<()> {
-    let button = lua.globals().get::<_, AnyUserData>("button")?;
-    let table = button.get_user_value::<Table>()?;
-    table.set(INDEX_MISS_FUNCTION, func)
+/// Gets the meta table of the table of the mouse object, which Lua can
+/// replace.
+fn table_meta<'lua>(table: &Table<'lua>) -> rlua::Result<Table<'lua>> {
+    table.get_metatable()
+         .ok_or_else(|| rlua::Error::RuntimeError("mouse had no meta table".into()))
 }
 
-fn set_newindex_miss(lua: &Lua, func: rlua::Function) -> rlua::Result<()> {
-    let button = lua.globals().get::<_, AnyUserData>("button")?;
-    let table = button.get_user_value::<Table>()?;
-    table.set(NEWINDEX_MISS_FUNCTION, func)
+fn set_index_miss(lua: &Lua, func: Function) -> rlua::Result<()> {
+    mouse_meta(lua)?.set(INDEX_MISS_FUNCTION, func)
+}
+
+fn set_newindex_miss(lua: &Lua, func: Function) -> rlua::Result<()> {
+    mouse_meta(lua)?.set(NEWINDEX_MISS_FUNCTION, func)
 }
 
 fn index<'lua>(lua: &'lua Lua,
                (mouse, index): (AnyUserData<'lua>, Value<'lua>))
                -> rlua::Result<Value<'lua>> {
-    use super::screen::SCREENS_HANDLE;
     let obj_table = mouse.get_user_value::<Table>()?;
-    match index {
-        Value::String(ref string) => {
-            let string = string.to_str()?;
-            if string != "screen" {
-                return obj_table.get(string)
-                // TODO call miss index handler if it exists
-            }
-            // TODO Might need a more robust way to get the current output...
-            // E.g they look at where the cursor is, I don't think we need to do that.
-
-            let outputs = OUTPUTS.lock().expect("Outputs was poisoned");
-            let index = outputs.iter()
-                .position(|output| output.focused)
-            // NOTE Best to just lie because no one handles nil screens properly
-                .unwrap_or(0);
-            let screens = lua.named_registry_value::<Vec<AnyUserData>>(SCREENS_HANDLE)?;
-            if index < screens.len() {
-                return screens[index].clone().to_lua(lua)
+    if let Value::String(ref string) = index {
+        match string.to_str()? {
+            "screen" => return screen_under_pointer(lua),
+            "current_client" => return client_under_pointer(lua),
+            "current_wibox" => return drawin_under_pointer(lua),
+            _ => {}
+        }
+    }
+    match obj_table.get(index.clone())? {
+        Value::Nil => {}
+        value => return Ok(value)
+    }
+    let meta = table_meta(&obj_table)?;
+    match meta.get::<_, Function>(INDEX_MISS_FUNCTION) {
+        Ok(function) => function.call((mouse, index)),
+        Err(_) => Ok(Value::Nil)
+    }
+}
+
+fn newindex<'lua>(lua: &'lua Lua,
+                  (mouse, index, value): (AnyUserData<'lua>, Value<'lua>, Value<'lua>))
+                  -> rlua::Result<()> {
+    let obj_table = mouse.get_user_value::<Table>()?;
+    let meta = table_meta(&obj_table)?;
+    match meta.get::<_, Function>(NEWINDEX_MISS_FUNCTION) {
+        Ok(function) => function.call((mouse, index, value)),
+        Err(_) => signal::global_emit_signal(lua, ("debug::newindex::miss".into(), index))
+    }
+}
+
+/// Gets the screen the pointer is on.
+///
+/// If the pointer is somehow on no screen then the first screen is returned,
+/// because no one handles nil screens properly.
+fn screen_under_pointer<'lua>(lua: &'lua Lua) -> rlua::Result<Value<'lua>> {
+    let pointer = POINTER.lock().expect("Lock was poisoned").clone();
+    let (x, y) = pointer.position;
+    let screens = lua.named_registry_value::<Vec<AnyUserData>>(SCREENS_HANDLE)?;
+    let mut geometry_match = None;
+    for screen in screens.iter() {
+        let state = Screen::cast(screen.clone().into())?.state()?;
+        if let Some(ref output_name) = pointer.output {
+            if state.outputs.iter().any(|output| output.name == *output_name) {
+                return screen.clone().to_lua(lua)
             }
-            // TODO Return screen even in bad case,
-            // see how awesome does it for maximal compatibility
-            panic!("Could not find a screen")
         }
-        _ => {}
+        let Area { origin: Origin { x: screen_x, y: screen_y },
+                   size: Size { width, height } } = state.geometry;
+        if geometry_match.is_none() && x >= screen_x as f64 && x < (screen_x + width) as f64
+           && y >= screen_y as f64 && y < (screen_y + height) as f64
+        {
+            geometry_match = Some(screen.clone());
+        }
+    }
+    match geometry_match.or_else(|| screens.into_iter().next()) {
+        Some(screen) => screen.to_lua(lua),
+        None => Ok(Value::Nil)
+    }
+}
+
+/// Gets the client under the pointer, if there is one.
+fn client_under_pointer<'lua>(lua: &'lua Lua) -> rlua::Result<Value<'lua>> {
+    let view_id = POINTER.lock().expect("Lock was poisoned").view;
+    match view_id {
+        Some(view_id) => client_by_view(lua, view_id)?.to_lua(lua),
+        None => Ok(Value::Nil)
+    }
+}
+
+/// Gets the drawin under the pointer, if there is one.
+fn drawin_under_pointer<'lua>(lua: &'lua Lua) -> rlua::Result<Value<'lua>> {
+    let (x, y) = POINTER.lock().expect("Lock was poisoned").position;
+    drawin_at(lua, x, y)?.to_lua(lua)
+}
+
+/// Gets the drawin or the client under the pointer, if there is one.
+fn object_under_pointer<'lua>(lua: &'lua Lua, _: ()) -> rlua::Result<Value<'lua>> {
+    match drawin_under_pointer(lua)? {
+        Value::Nil => client_under_pointer(lua),
+        drawin => Ok(drawin)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::mouse;
+    use rlua::{AnyUserData, Lua};
+
+    #[test]
+    fn mouse_index_miss_handler() {
+        let lua = Lua::new();
+        mouse::init(&lua).unwrap();
+        lua.eval(
+            r#"
+local called = 0
+mouse.set_index_miss_handler(function(m, key)
+    assert(m == mouse)
+    assert(key == "current_widget")
+    called = called + 1
+    return "a widget"
+end)
+
+assert(mouse.current_widget == "a widget")
+assert(called == 1)
+"#,
+            None
+        ).unwrap()
+    }
+
+    #[test]
+    fn mouse_index_miss_handler_not_called_for_known() {
+        let lua = Lua::new();
+        mouse::init(&lua).unwrap();
+        lua.eval(
+            r#"
+local called = 0
+mouse.set_index_miss_handler(function()
+    called = called + 1
+end)
+
+assert(type(mouse.coords) == "function")
+assert(called == 0)
+"#,
+            None
+        ).unwrap()
+    }
+
+    #[test]
+    fn mouse_index_miss_no_handler() {
+        let lua = Lua::new();
+        mouse::init(&lua).unwrap();
+        lua.eval(
+            r#"
+assert(type(mouse.current_widget) == "nil")
+"#,
+            None
+        ).unwrap()
+    }
+
+    #[test]
+    fn mouse_newindex_miss_handler() {
+        let lua = Lua::new();
+        mouse::init(&lua).unwrap();
+        lua.eval(
+            r#"
+local values = {}
+mouse.set_newindex_miss_handler(function(m, key, value)
+    assert(m == mouse)
+    values[key] = value
+end)
+mouse.set_index_miss_handler(function(m, key)
+    return values[key]
+end)
+
+mouse.current_widget = "a widget"
+assert(values.current_widget == "a widget")
+assert(mouse.current_widget == "a widget")
+"#,
+            None
+        ).unwrap()
+    }
+
+    #[test]
+    fn mouse_coords_returns_new_position() {
+        let lua = Lua::new();
+        mouse::init(&lua).unwrap();
+        lua.eval::<()>(
+            r#"
+local coords = mouse.coords({ x = 5, y = 6 })
+assert(coords.x == 5 and coords.y == 6)
+assert(not pcall(mouse.coords, { x = 0/0 }))
+assert(not pcall(mouse.coords, { y = math.huge }))
+"#,
+            None
+        ).unwrap()
+    }
+
+    #[test]
+    fn mouse_without_meta_table_errors() {
+        let lua = Lua::new();
+        mouse::init(&lua).unwrap();
+        lua.globals()
+           .get::<_, AnyUserData>("mouse")
+           .unwrap()
+           .set_user_value(lua.create_table().unwrap())
+           .unwrap();
+        lua.eval::<()>(
+            r#"
+assert(not pcall(function() return mouse.foo end))
+assert(not pcall(function() mouse.foo = 1 end))
+"#,
+            None
+        ).unwrap()
+    }
+
+    #[test]
+    fn mouse_miss_handler_not_on_button() {
+        let lua = Lua::new();
+        mouse::init(&lua).unwrap();
+        lua.eval(
+            r#"
+button = {}
+mouse.set_index_miss_handler(function() return 1 end)
+mouse.set_newindex_miss_handler(function() end)
+assert(next(button) == nil)
+"#,
+            None
+        ).unwrap()
     }
-    return obj_table.get(index)
 }
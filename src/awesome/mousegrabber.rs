@@ -1,8 +1,7 @@
 //! AwesomeWM Mousegrabber interface
 
-use awesome::LUA;
+use awesome::{LUA, POINTER};
 use rlua::{self, Function, Lua, Value};
-use wlroots::wlr_button_state;
 
 pub const MOUSEGRABBER_TABLE: &str = "mousegrabber";
 const MOUSEGRABBER_CALLBACK: &str = "__callback";
@@ -18,21 +17,21 @@ pub fn init(lua: &Lua) -> rlua::Result<()> {
     globals.set(MOUSEGRABBER_TABLE, mousegrabber_table)
 }
 
-pub fn mousegrabber_handle(x: i32,
-                           y: i32,
-                           button: Option<(u32, wlr_button_state)>)
-                           -> rlua::Result<()> {
+/// Call the Lua callback grabbing the mouse with the position of the
+/// pointer and the buttons that are held down.
+pub fn mousegrabber_handle(x: i32, y: i32) -> rlua::Result<()> {
+    let buttons = POINTER.lock().expect("Pointer was poisoned").buttons.clone();
     LUA.with(|lua| {
                  let lua = lua.borrow_mut();
-                 let button_events =
-                     button.map(|(button, button_state)| {
-                                    ::lua::mouse_events_to_lua(&*lua, button, button_state)
-                                })
-                           .unwrap_or_else(|| Ok(vec![false, false, false, false, false]))?;
-                 call_mousegrabber(&*lua, (x, y, button_events))
+                 call_mousegrabber(&*lua, (x, y, ::lua::pressed_buttons_to_lua(&buttons)))
              })
 }
 
+/// Whether a Lua callback is currently grabbing the mouse.
+pub fn mousegrabber_running() -> bool {
+    LUA.with(|lua| isrunning(&*lua.borrow(), ()).unwrap_or(false))
+}
+
 fn call_mousegrabber(lua: &Lua, (x, y, button_events): (i32, i32, Vec<bool>)) -> rlua::Result<()> {
     let lua_callback = match lua.named_registry_value::<Function>(MOUSEGRABBER_CALLBACK) {
         Ok(function) => function,
@@ -65,7 +64,7 @@ fn stop(lua: &Lua, _: ()) -> rlua::Result<()> {
 }
 
 fn isrunning(lua: &Lua, _: ()) -> rlua::Result<bool> {
-    match lua.named_registry_value::<Value>(MOUSEGRABBER_TABLE)? {
+    match lua.named_registry_value::<Value>(MOUSEGRABBER_CALLBACK)? {
         Value::Function(_) => Ok(true),
         _ => Ok(false)
     }
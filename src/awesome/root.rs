@@ -1,11 +1,23 @@
 //! TODO Fill in
 
+use super::button::Button;
 use super::class::{Class, ClassBuilder};
+use super::key::Key;
 use super::object::{self, Object, Objectable};
+use super::signal;
 use cairo_sys::cairo_pattern_t;
-use rlua::{self, LightUserData, Lua, Table, ToLua, UserData, UserDataMethods, Value};
+use awesome::lua::{surface_to_lua, LUA};
+use compositor::{self, ContentSource};
+use rlua::{self, AnyUserData, Integer, LightUserData, Lua, Table, ToLua, UserData,
+           UserDataMethods, Value};
 use std::default::Default;
 use std::fmt::{self, Display, Formatter};
+use wlroots::xkbcommon::xkb;
+
+/// The global key bindings.
+const ROOT_KEYS: &'static str = "__root_keys";
+/// The button bindings of the root window, i.e where there is no client.
+const ROOT_BUTTONS: &'static str = "__root_buttons";
 
 #[derive(Clone, Debug)]
 pub struct RootState {
@@ -51,13 +63,14 @@ fn method_setup<'lua>(lua: &'lua Lua,
     // TODO Do properly
     use super::dummy;
     builder.method("connect_signal".into(), lua.create_function(dummy)?)?
-           .method("buttons".into(), lua.create_function(dummy)?)?
+           .method("buttons".into(), lua.create_function(buttons)?)?
            .method("wallpaper".into(), lua.create_function(wallpaper)?)?
            .method("tags".into(), lua.create_function(tags)?)?
-           .method("keys".into(), lua.create_function(dummy)?)?
+           .method("keys".into(), lua.create_function(keys)?)?
            .method("size".into(), lua.create_function(dummy_double)?)?
            .method("size_mm".into(), lua.create_function(dummy_double)?)?
-           .method("cursor".into(), lua.create_function(dummy)?)
+           .method("cursor".into(), lua.create_function(cursor)?)?
+           .method("content".into(), lua.create_function(content)?)
 }
 
 impl_objectable!(Root, RootState);
@@ -83,6 +96,98 @@ fn set_wallpaper<'lua>(_: &'lua Lua, _pattern: *mut cairo_pattern_t) -> rlua::Re
     Ok(true)
 }
 
+/// Sets the cursor image shown when the cursor is not over a client.
+fn cursor<'lua>(_: &'lua Lua, cursor_name: String) -> rlua::Result<()> {
+    compositor::set_root_cursor(cursor_name);
+    Ok(())
+}
+
+/// Gets what was on every output when it was last captured, as a cairo
+/// surface, or nil if it wasn't captured yet.
+///
+/// Reading it asks for a new capture, which `content::changed` announces.
+fn content<'lua>(lua: &'lua Lua, _: ()) -> rlua::Result<Value<'lua>> {
+    surface_to_lua(lua, compositor::content(ContentSource::Root))
+}
+
+/// Gets the global key bindings, or sets them if a table of keys is given.
+fn keys<'lua>(lua: &'lua Lua, keys: Option<Table<'lua>>) -> rlua::Result<Table<'lua>> {
+    bindings(lua, ROOT_KEYS, keys)
+}
+
+/// Gets the button bindings of the root window, or sets them if a table of
+/// buttons is given.
+fn buttons<'lua>(lua: &'lua Lua, buttons: Option<Table<'lua>>) -> rlua::Result<Table<'lua>> {
+    bindings(lua, ROOT_BUTTONS, buttons)
+}
+
+fn bindings<'lua>(lua: &'lua Lua,
+                  name: &'static str,
+                  bindings: Option<Table<'lua>>)
+                  -> rlua::Result<Table<'lua>> {
+    if let Some(bindings) = bindings {
+        lua.set_named_registry_value(name, bindings)?;
+    }
+    match lua.named_registry_value::<Value>(name)? {
+        Value::Table(bindings) => Ok(bindings),
+        _ => lua.create_table()
+    }
+}
+
+/// Emit press or release on the global key bindings triggered by the keysym
+/// while the modifiers are active.
+///
+/// Returns true if a binding was triggered, in which case the key should not
+/// be sent to the focused client.
+pub fn root_key(keysym: xkb::Keysym, mods: u32, pressed: bool) -> bool {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 match emit_key(&*lua, keysym, mods, pressed) {
+                     Ok(triggered) => triggered,
+                     Err(err) => {
+                         warn!("Could not run key bindings: {}", err);
+                         false
+                     }
+                 }
+             })
+}
+
+/// Emit press or release on the button bindings of the root window
+/// triggered by the button while the modifiers are active.
+pub fn root_button(button: u32, mods: u32, pressed: bool) {
+    LUA.with(|lua| {
+                 let lua = lua.borrow();
+                 if let Err(err) = emit_button(&*lua, button, mods, pressed) {
+                     warn!("Could not run button bindings: {}", err);
+                 }
+             })
+}
+
+fn emit_key(lua: &Lua, keysym: xkb::Keysym, mods: u32, pressed: bool) -> rlua::Result<bool> {
+    let name = if pressed { "press" } else { "release" };
+    let mut triggered = false;
+    for pair in bindings(lua, ROOT_KEYS, None)?.pairs::<Integer, AnyUserData>() {
+        let (_, key) = pair?;
+        if Key::cast(key.clone().into())?.matches(keysym, mods)? {
+            signal::emit_object_signal(lua, key.into(), name.into(), ())?;
+            triggered = true;
+        }
+    }
+    Ok(triggered)
+}
+
+fn emit_button(lua: &Lua, button: u32, mods: u32, pressed: bool) -> rlua::Result<()> {
+    let name = if pressed { "press" } else { "release" };
+    let button = ::lua::button_to_lua(button);
+    for pair in bindings(lua, ROOT_BUTTONS, None)?.pairs::<Integer, AnyUserData>() {
+        let (_, binding) = pair?;
+        if Button::cast(binding.clone().into())?.matches(button, mods)? {
+            signal::emit_object_signal(lua, binding.into(), name.into(), ())?;
+        }
+    }
+    Ok(())
+}
+
 fn tags<'lua>(lua: &'lua Lua, _: ()) -> rlua::Result<Table<'lua>> {
     let table = lua.create_table()?;
     let activated_tags = lua.named_registry_value::<Table>(super::tag::TAG_LIST)?;
@@ -95,9 +200,13 @@ fn tags<'lua>(lua: &'lua Lua, _: ()) -> rlua::Result<Table<'lua>> {
 
 #[cfg(test)]
 mod test {
-    use super::super::root;
-    use super::super::tag;
+    use super::super::{button, key, root};
+    use super::super::{tag, CONTENTS};
+    use compositor::ContentSource;
+    use ipc::Content;
     use rlua::Lua;
+    use wlroots::pointer_events::{BTN_LEFT, BTN_RIGHT};
+    use wlroots::xkbcommon::xkb::{KEY_Escape, KEY_Return};
 
     #[test]
     fn tags_none() {
@@ -161,6 +270,82 @@ first.activated = false
 local t = root.tags()
 assert(t[1] == second)
 assert(type(t[2]) == "nil")
+"#,
+            None
+        ).unwrap()
+    }
+
+    #[test]
+    fn root_key_fires_binding() {
+        let lua = Lua::new();
+        key::init(&lua).unwrap();
+        root::init(&lua).unwrap();
+        lua.eval::<()>(
+            r#"
+pressed = 0
+local k = key{ modifiers = { "Mod4" }, key = "Return" }
+k:connect_signal("press", function() pressed = pressed + 1 end)
+root.keys({ k })
+assert(root.keys()[1] == k)
+"#,
+            None
+        ).unwrap();
+        let mod4 = 1 << 6;
+        let lock = 1 << 1;
+        assert!(!root::emit_key(&lua, KEY_Return, 0, true).unwrap());
+        assert!(root::emit_key(&lua, KEY_Return, mod4, true).unwrap());
+        // Caps Lock doesn't stop bindings from triggering.
+        assert!(root::emit_key(&lua, KEY_Return, mod4 | lock, true).unwrap());
+        assert!(!root::emit_key(&lua, KEY_Escape, mod4, true).unwrap());
+        lua.eval::<()>("assert(pressed == 2)", None).unwrap()
+    }
+
+    #[test]
+    fn root_button_fires_binding() {
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        root::init(&lua).unwrap();
+        lua.eval::<()>(
+            r#"
+released = false
+local b = button{ button = 1 }
+b:connect_signal("release", function() released = true end)
+root.buttons({ b })
+"#,
+            None
+        ).unwrap();
+        root::emit_button(&lua, BTN_RIGHT, 0, false).unwrap();
+        lua.eval::<()>("assert(not released)", None).unwrap();
+        root::emit_button(&lua, BTN_LEFT, 0, false).unwrap();
+        lua.eval::<()>("assert(released)", None).unwrap()
+    }
+
+    #[test]
+    fn content_is_last_capture() {
+        let lua = Lua::new();
+        tag::init(&lua).unwrap();
+        root::init(&lua).unwrap();
+        // Nothing was captured yet, a capture is only asked for.
+        lua.eval::<()>("assert(root.content() == nil)", None).unwrap();
+        let content = Content { width: 2,
+                                height: 1,
+                                data: vec![0; 8],
+                                fresh: true };
+        CONTENTS.lock().unwrap().push((ContentSource::Root, content));
+        lua.eval::<()>(
+            r#"
+local content = root.content()
+assert(type(content) == "userdata")
+assert(content:pointer() ~= nil)
+"#,
+            None
+        ).unwrap();
+        assert!(!CONTENTS.lock().unwrap()[0].1.fresh);
+        // Reading it again gives the same capture until a new one is there.
+        lua.eval::<()>(
+            r#"
+assert(root.content() ~= nil)
+collectgarbage()
 "#,
             None
         ).unwrap()
@@ -1,7 +1,8 @@
 //! TODO Fill in
 
 use awesome::{class::{self, Class, ClassBuilder}, object::{self, Object, Objectable},
-              property::Property, OUTPUTS};
+              lua::surface_to_lua, property::Property, OUTPUTS};
+use compositor::{self, ContentSource};
 use ipc;
 use rlua::{self, AnyUserData, Lua, MetaMethod, Table, ToLua, UserData, UserDataMethods, Value};
 use std::default::Default;
@@ -78,7 +79,9 @@ impl UserData for ScreenState {
 impl<'lua> Screen<'lua> {
     fn new(lua: &Lua) -> rlua::Result<Object> {
         let class = class::class_setup(lua, "screen")?;
-        Ok(Screen::allocate(lua, class)?.build())
+        let table = lua.create_table()?;
+        table.set("set_gamma", lua.create_function(set_gamma)?)?;
+        Ok(Screen::allocate(lua, class)?.add_to_meta(table)?.build())
     }
 
     fn init_screens(&mut self, output: ipc::Output, outputs: Vec<Output>) -> rlua::Result<()> {
@@ -165,6 +168,10 @@ fn property_setup<'lua>(lua: &'lua Lua,
            .property(Property::new("workarea".into(),
                                    None,
                                    Some(lua.create_function(get_workarea)?),
+                                   None))?
+           .property(Property::new("content".into(),
+                                   None,
+                                   Some(lua.create_function(get_content)?),
                                    None))
 }
 
@@ -178,6 +185,53 @@ fn get_workarea<'lua>(lua: &'lua Lua, object: AnyUserData<'lua>) -> rlua::Result
     screen.get_workarea(lua)
 }
 
+/// Gets what was on the screen when it was last captured, as a cairo surface,
+/// or nil if it wasn't captured yet.
+///
+/// Reading it asks for a new capture, which `content::changed` announces.
+fn get_content<'lua>(lua: &'lua Lua, object: AnyUserData<'lua>) -> rlua::Result<Value<'lua>> {
+    let screen = Screen::cast(object.into())?;
+    let name = match screen.state()?.outputs.first() {
+        Some(output) => output.name.clone(),
+        None => return Ok(Value::Nil)
+    };
+    surface_to_lua(lua, compositor::content(ContentSource::Output(name)))
+}
+
+/// Sets the gamma of the screen from how much of each channel to keep,
+/// between 0 and 1, and an optional gamma correction.
+///
+/// Without any arguments the gamma the screen had originally is restored.
+fn set_gamma<'lua>(_: &'lua Lua,
+                   (object, red, green, blue, gamma): (AnyUserData<'lua>,
+                                                       Option<f64>,
+                                                       Option<f64>,
+                                                       Option<f64>,
+                                                       Option<f64>))
+                   -> rlua::Result<()> {
+    let screen = Screen::cast(object.into())?;
+    let factors = match (red, green, blue) {
+        (None, None, None) => None,
+        (Some(red), Some(green), Some(blue)) => {
+            if !red.is_finite() || !green.is_finite() || !blue.is_finite() {
+                return Err(rlua::Error::RuntimeError("red, green and blue must be numbers".into()))
+            }
+            let gamma = gamma.unwrap_or(1.0);
+            if !gamma.is_finite() || gamma <= 0.0 {
+                return Err(rlua::Error::RuntimeError("gamma must be positive".into()))
+            }
+            Some(((red, green, blue), gamma))
+        }
+        _ => {
+            return Err(rlua::Error::RuntimeError("set_gamma needs red, green and blue".into()))
+        }
+    };
+    for output in &screen.state()?.outputs {
+        compositor::set_gamma(output.name.clone(), factors);
+    }
+    Ok(())
+}
+
 fn count<'lua>(lua: &'lua Lua, _: ()) -> rlua::Result<Value<'lua>> {
     let screens = lua.named_registry_value::<Vec<AnyUserData>>(SCREENS_HANDLE)?;
     Ok(Value::Integer(screens.len() as _))
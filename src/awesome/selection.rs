@@ -0,0 +1,47 @@
+//! The `selection` function, which gets the text of the selection.
+
+use super::SELECTION;
+use rlua::{self, Lua};
+
+pub fn init(lua: &Lua) -> rlua::Result<()> {
+    lua.globals().set("selection", lua.create_function(selection)?)
+}
+
+/// Gets the text of the primary selection, or of the clipboard if
+/// "clipboard" is given.
+///
+/// Returns nil if there is no selection or it isn't text.
+fn selection<'lua>(_: &'lua Lua, name: Option<String>) -> rlua::Result<Option<String>> {
+    let selection = SELECTION.lock().expect("Selection was poisoned");
+    match name.as_ref().map(String::as_str) {
+        None | Some("primary") => Ok(selection.primary.clone()),
+        Some("clipboard") => Ok(selection.clipboard.clone()),
+        Some(name) => Err(rlua::Error::RuntimeError(format!("{} is not a selection", name)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{selection, SELECTION};
+    use rlua::Lua;
+
+    #[test]
+    fn selection_reads_primary_and_clipboard() {
+        let lua = Lua::new();
+        selection::init(&lua).unwrap();
+        {
+            let mut selection = SELECTION.lock().unwrap();
+            selection.primary = Some("selected".into());
+            selection.clipboard = None;
+        }
+        lua.eval(
+            r#"
+assert(selection() == "selected")
+assert(selection("primary") == "selected")
+assert(selection("clipboard") == nil)
+assert(not pcall(selection, "secondary"))
+"#,
+            None
+        ).unwrap()
+    }
+}
@@ -101,7 +101,9 @@ pub fn global_disconnect_signal<'lua>(lua: &'lua Lua, name: String) -> rlua::Res
 }
 
 /// Emit the signal with the given name from the global signal list.
-pub fn global_emit_signal<'lua>(lua: &'lua Lua, (name, args): (String, Value)) -> rlua::Result<()> {
+pub fn global_emit_signal<'lua, A>(lua: &'lua Lua, (name, args): (String, A)) -> rlua::Result<()>
+    where A: ToLuaMulti<'lua> + Clone
+{
     let global_signals = lua.named_registry_value::<Table>(GLOBAL_SIGNALS)?;
     emit_signals(lua, global_signals, name, args)
 }
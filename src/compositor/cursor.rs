@@ -1,4 +1,12 @@
-use wlroots::CursorHandler;
+use compositor::{self, Server};
+use std::env;
+use wlroots::{CursorHandle, CursorHandler, HandleResult, XCursorManager};
+
+/// The size of the cursor if `XCURSOR_SIZE` is not set.
+const DEFAULT_CURSOR_SIZE: u32 = 16;
+/// The image of the cursor when it is not over a client.
+const DEFAULT_ROOT_CURSOR: &'static str = "left_ptr";
+
 #[derive(Debug, Default)]
 pub struct CursorManager;
 
@@ -9,3 +17,124 @@ impl CursorManager {
         CursorManager::default()
     }
 }
+
+/// The xcursor theme, loaded at the scale of every output.
+#[derive(Debug)]
+pub struct CursorTheme {
+    /// The loaded theme, if any could be loaded. Without one the cursor has
+    /// no image.
+    manager: Option<XCursorManager>,
+    size: u32,
+    /// The scales the theme has been loaded at.
+    scales: Vec<f32>,
+    /// The name of the cursor image used when not over a client.
+    root_cursor: String
+}
+
+impl Default for CursorTheme {
+    fn default() -> Self {
+        let name = env::var("XCURSOR_THEME").ok();
+        let size = env::var("XCURSOR_SIZE").ok()
+                                           .and_then(|size| size.parse().ok())
+                                           .filter(|&size| size > 0)
+                                           .unwrap_or(DEFAULT_CURSOR_SIZE);
+        CursorTheme::new(name, size)
+    }
+}
+
+impl CursorTheme {
+    /// Create a theme with the given name and size.
+    ///
+    /// If no name is given then the default theme is used, which is also
+    /// used if the theme can't be loaded.
+    pub fn new(name: Option<String>, size: u32) -> Self {
+        let manager = XCursorManager::create(name.clone(), size).or_else(|| {
+            warn!("Could not load cursor theme {:?}, using the default theme",
+                  name);
+            XCursorManager::create(None, size)
+        });
+        if manager.is_none() {
+            warn!("Could not load the default cursor theme, the cursor has no image");
+        }
+        let mut theme = CursorTheme { manager,
+                                      size,
+                                      scales: vec![],
+                                      root_cursor: DEFAULT_ROOT_CURSOR.into() };
+        theme.load_scale(1.0);
+        theme
+    }
+
+    /// Load the theme at the scale, so it looks right on outputs with that
+    /// scale.
+    pub fn load_scale(&mut self, scale: f32) {
+        if self.scales.contains(&scale) {
+            return
+        }
+        let loaded = match self.manager {
+            Some(ref mut manager) => manager.load(scale),
+            None => return
+        };
+        if !loaded {
+            warn!("Could not load cursor theme at scale {}", scale);
+            return
+        }
+        self.scales.push(scale);
+    }
+
+    /// Set the cursor back to the root cursor image.
+    pub fn reset_image(&mut self, cursor: &mut CursorHandle) -> HandleResult<()> {
+        let CursorTheme { ref mut manager,
+                          ref root_cursor,
+                          .. } = *self;
+        let manager = match *manager {
+            Some(ref mut manager) => manager,
+            None => return Ok(())
+        };
+        run_handles!([(cursor: {cursor})] => {
+            manager.set_cursor_image(root_cursor.clone(), cursor);
+        })
+    }
+}
+
+/// Replace the cursor theme, keeping the root cursor image.
+///
+/// If the size isn't given the old size is kept.
+pub fn set_cursor_theme(name: Option<String>, size: Option<u32>) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut cursor,
+                     ref mut cursor_theme,
+                     .. } = *server;
+        let size = size.unwrap_or(cursor_theme.size);
+        let mut new_theme = CursorTheme::new(name, size);
+        for scale in cursor_theme.scales.iter() {
+            new_theme.load_scale(*scale);
+        }
+        new_theme.root_cursor = cursor_theme.root_cursor.clone();
+        *cursor_theme = new_theme;
+        cursor_theme.reset_image(cursor).expect("Cursor was destroyed");
+    });
+}
+
+/// Set the image of the cursor when it is not over a client.
+pub fn set_root_cursor(name: String) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut cursor,
+                     ref mut cursor_theme,
+                     ref seat,
+                     .. } = *server;
+        let found = match cursor_theme.manager {
+            Some(ref mut manager) => manager.get_xcursor(name.clone(), None).is_some(),
+            None => false
+        };
+        if !found {
+            warn!("Cursor theme has no cursor named {}", name);
+            return
+        }
+        cursor_theme.root_cursor = name;
+        if seat.hovered.is_none() {
+            cursor_theme.reset_image(cursor).expect("Cursor was destroyed");
+        }
+    });
+}
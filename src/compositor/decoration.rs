@@ -0,0 +1,424 @@
+//! Decorations of views, negotiated with xdg-decoration.
+//!
+//! Lua can pick if a client decorates itself or if the compositor does.
+//! Otherwise the client gets what it asked for, which is decorating itself
+//! if it didn't ask. When the compositor decorates a view it draws the
+//! titlebars Lua drew for it around the surface of the view.
+//!
+//! Titlebars are part of the geometry of a client, so when they change the
+//! surface is resized to keep the client the same size. Borders are drawn
+//! around every view, outside of its titlebars.
+
+use awesome;
+use compositor::{self, Server, Shell, View};
+use wlroots::{Area, Compositor, HandleResult, Origin, Size, XdgDecorationManagerHandler,
+              XdgToplevelDecoration, XdgToplevelDecorationHandle, XdgToplevelDecorationHandler,
+              XdgToplevelDecorationMode};
+
+/// The sides of a view that can have a titlebar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TitlebarSide {
+    Top,
+    Right,
+    Bottom,
+    Left
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decorations {
+    /// The decoration of the view, if its client uses xdg-decoration.
+    pub handle: Option<XdgToplevelDecorationHandle>,
+    /// The mode the client asked for.
+    pub requested: Option<XdgToplevelDecorationMode>,
+    /// The mode Lua picked, which wins over the mode the client asked for.
+    pub preferred: Option<XdgToplevelDecorationMode>,
+    /// The size of the titlebars, in the order of `TitlebarSide`.
+    pub titlebars: [i32; 4],
+    /// The areas of the titlebars Lua was last told about, relative to the
+    /// client.
+    pub titlebar_areas: [Area; 4],
+    /// The ids of the drawables Lua draws the titlebars in.
+    pub titlebar_drawables: [Option<usize>; 4],
+    pub border_width: i32,
+    /// The color of the border as RGBA.
+    pub border_color: [u8; 4]
+}
+
+#[derive(Debug, Default)]
+pub struct DecorationManager;
+
+#[derive(Debug, Default)]
+pub struct Decoration;
+
+impl TitlebarSide {
+    pub fn all() -> [TitlebarSide; 4] {
+        [TitlebarSide::Top, TitlebarSide::Right, TitlebarSide::Bottom, TitlebarSide::Left]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            TitlebarSide::Top => "top",
+            TitlebarSide::Right => "right",
+            TitlebarSide::Bottom => "bottom",
+            TitlebarSide::Left => "left"
+        }
+    }
+}
+
+impl Default for Decorations {
+    fn default() -> Self {
+        Decorations { handle: None,
+                      requested: None,
+                      preferred: None,
+                      titlebars: [0; 4],
+                      titlebar_areas: [Area::default(); 4],
+                      titlebar_drawables: [None; 4],
+                      border_width: 0,
+                      border_color: [0, 0, 0, 0xff] }
+    }
+}
+
+impl Decorations {
+    /// The mode the view is decorated with.
+    pub fn mode(&self) -> XdgToplevelDecorationMode {
+        self.preferred
+            .or(self.requested)
+            .unwrap_or(XdgToplevelDecorationMode::ClientSide)
+    }
+
+    /// If the compositor draws the titlebars of the view.
+    pub fn server_side(&self) -> bool {
+        self.mode() == XdgToplevelDecorationMode::ServerSide
+    }
+
+    /// The size of the titlebars that are drawn, as top, right, bottom and
+    /// left.
+    pub fn insets(&self) -> (i32, i32, i32, i32) {
+        if !self.server_side() {
+            return (0, 0, 0, 0)
+        }
+        (self.titlebars[0], self.titlebars[1], self.titlebars[2], self.titlebars[3])
+    }
+
+    /// Where the surface is, relative to the origin of the view.
+    pub fn surface_offset(&self) -> Origin {
+        let (top, _, _, left) = self.insets();
+        Origin::new(left, top)
+    }
+
+    /// The size of the client with a surface of the size, which includes its
+    /// titlebars.
+    pub fn client_size(&self, surface_size: Size) -> Size {
+        let (top, right, bottom, left) = self.insets();
+        Size::new(left + surface_size.width + right, top + surface_size.height + bottom)
+    }
+
+    /// The area of the titlebar on the side, relative to the origin of a
+    /// client of the size.
+    ///
+    /// The top and bottom titlebars span the left and right titlebars too.
+    /// Titlebars that don't fit in the client are cut off.
+    pub fn titlebar_area(&self, side: TitlebarSide, size: Size) -> Area {
+        let (top, right, bottom, left) = self.insets();
+        let (width, height) = (size.width.max(0), size.height.max(0));
+        let top = top.min(height);
+        let bottom = bottom.min(height - top);
+        let left = left.min(width);
+        let right = right.min(width - left);
+        let side_height = height - top - bottom;
+        match side {
+            TitlebarSide::Top => Area::new(Origin::new(0, 0), Size::new(width, top)),
+            TitlebarSide::Right => {
+                Area::new(Origin::new(width - right, top), Size::new(right, side_height))
+            }
+            TitlebarSide::Bottom => {
+                Area::new(Origin::new(0, height - bottom), Size::new(width, bottom))
+            }
+            TitlebarSide::Left => Area::new(Origin::new(0, top), Size::new(left, side_height))
+        }
+    }
+
+    /// The area of the client with its border, relative to the origin of a
+    /// client of the size.
+    pub fn frame_area(&self, size: Size) -> Area {
+        let border = self.border_width;
+        Area::new(Origin::new(-border, -border),
+                  Size::new(size.width + 2 * border, size.height + 2 * border))
+    }
+
+    /// The areas of the border around a client of the size, relative to its
+    /// origin, as top, bottom, left and right.
+    pub fn border_areas(&self, size: Size) -> [Area; 4] {
+        let border = self.border_width.max(0);
+        let Area { origin: Origin { x, y },
+                   size: Size { width, height } } = self.frame_area(size);
+        let (width, height) = (width.max(0), height.max(0));
+        let side_height = (height - 2 * border).max(0);
+        [Area::new(Origin::new(x, y), Size::new(width, border.min(height))),
+         Area::new(Origin::new(x, (y + height - border).max(y)),
+                   Size::new(width, border.min(height))),
+         Area::new(Origin::new(x, y + border), Size::new(border.min(width), side_height)),
+         Area::new(Origin::new((x + width - border).max(x), y + border),
+                   Size::new(border.min(width), side_height))]
+    }
+
+    /// The color of the border, as the renderer wants it.
+    pub fn border_color(&self) -> [f32; 4] {
+        let mut color = [0.0; 4];
+        for (channel, value) in color.iter_mut().zip(self.border_color.iter()) {
+            *channel = *value as f32 / 255.0;
+        }
+        color
+    }
+
+    /// Get the titlebar at the view local coordinates of a client of the size.
+    ///
+    /// The coordinates returned are local to the titlebar found.
+    pub fn titlebar_at(&self, size: Size, (x, y): (f64, f64)) -> Option<(TitlebarSide, f64, f64)> {
+        if !self.server_side() {
+            return None
+        }
+        for &side in TitlebarSide::all().iter() {
+            let Area { origin, size } = self.titlebar_area(side, size);
+            let (tx, ty) = (x - origin.x as f64, y - origin.y as f64);
+            if tx >= 0.0 && ty >= 0.0 && tx < size.width as f64 && ty < size.height as f64 {
+                return Some((side, tx, ty))
+            }
+        }
+        None
+    }
+}
+
+impl DecorationManager {
+    pub fn new() -> Self {
+        DecorationManager::default()
+    }
+}
+
+impl XdgDecorationManagerHandler for DecorationManager {
+    fn new_decoration(&mut self,
+                      compositor: &mut Compositor,
+                      decoration: &mut XdgToplevelDecoration)
+                      -> Option<Box<XdgToplevelDecorationHandler>> {
+        let server: &mut Server = compositor.into();
+        let shell: Shell = decoration.surface().into();
+        let view = server.views.iter_mut().find(|view| view.shell == shell)?;
+        let insets = view.decorations.insets();
+        view.decorations.handle = Some(decoration.weak_reference());
+        // The client has to be told the mode even if it never asks for one.
+        update_decorations(view, insets)
+            .unwrap_or_else(|_| warn!("Could not set decoration mode, view was destroyed"));
+        Some(Box::new(Decoration))
+    }
+}
+
+impl XdgToplevelDecorationHandler for Decoration {
+    fn request_mode(&mut self,
+                    compositor: &mut Compositor,
+                    decoration: &mut XdgToplevelDecoration) {
+        let server: &mut Server = compositor.into();
+        let weak_reference = Some(decoration.weak_reference());
+        if let Some(view) = server.views
+                                  .iter_mut()
+                                  .find(|view| view.decorations.handle == weak_reference)
+        {
+            let insets = view.decorations.insets();
+            view.decorations.requested = decoration.client_mode();
+            update_decorations(view, insets)
+                .unwrap_or_else(|_| warn!("Could not set decoration mode, view was destroyed"))
+        }
+    }
+
+    fn destroyed(&mut self, compositor: &mut Compositor, decoration: &mut XdgToplevelDecoration) {
+        let server: &mut Server = compositor.into();
+        let weak_reference = Some(decoration.weak_reference());
+        if let Some(view) = server.views
+                                  .iter_mut()
+                                  .find(|view| view.decorations.handle == weak_reference)
+        {
+            let insets = view.decorations.insets();
+            view.decorations.handle = None;
+            view.decorations.requested = None;
+            fit_surface(view, insets)
+                .unwrap_or_else(|_| warn!("Could not resize view, it was destroyed"));
+            awesome::client_requests_no_titlebar(view.id, true);
+        }
+    }
+}
+
+/// Tell the client of the view how it's decorated, and Lua what the client
+/// asked for.
+///
+/// The insets are the size of the titlebars before the mode changed.
+fn update_decorations(view: &mut View, insets: (i32, i32, i32, i32)) -> HandleResult<()> {
+    let mode = view.decorations.mode();
+    if let Some(ref mut handle) = view.decorations.handle {
+        run_handles!([(handle: {handle})] => {
+            handle.set_mode(mode);
+        })?;
+    }
+    fit_surface(view, insets)?;
+    let requests_no_titlebar =
+        view.decorations.requested != Some(XdgToplevelDecorationMode::ServerSide);
+    awesome::client_requests_no_titlebar(view.id, requests_no_titlebar);
+    Ok(())
+}
+
+/// Resize the surface of the view so that the client stays the same size
+/// now that its titlebars changed from the insets, and let Lua know about
+/// the new titlebars.
+fn fit_surface(view: &mut View,
+               (old_top, old_right, old_bottom, old_left): (i32, i32, i32, i32))
+               -> HandleResult<()> {
+    let (top, right, bottom, left) = view.decorations.insets();
+    let grow_width = (left + right) - (old_left + old_right);
+    let grow_height = (top + bottom) - (old_top + old_bottom);
+    let Size { width, height } = view.shell.geometry()?.size;
+    // The client hasn't picked a size yet.
+    if (grow_width != 0 || grow_height != 0) && width > 0 && height > 0 {
+        view.shell
+            .set_size(Size::new((width - grow_width).max(1), (height - grow_height).max(1)))?;
+    }
+    update_titlebars(view)
+}
+
+/// Let Lua know about the titlebars of the view that moved or changed size,
+/// so it can resize their drawables and draw them again.
+///
+/// This is called when the titlebars or the surface of the view change,
+/// never while rendering.
+pub fn update_titlebars(view: &mut View) -> HandleResult<()> {
+    let client_size = view.client_size()?;
+    for &side in TitlebarSide::all().iter() {
+        let index = side as usize;
+        let area = view.decorations.titlebar_area(side, client_size);
+        if view.decorations.titlebar_areas[index] != area {
+            view.decorations.titlebar_areas[index] = area;
+            view.decorations.titlebar_drawables[index] =
+                awesome::titlebar_resized(view.id, side, area);
+        }
+    }
+    Ok(())
+}
+
+/// Let the compositor decorate the view with the id, or make its client do
+/// it. If `None` the client decides.
+pub fn set_server_side_decorations(view_id: usize, server_side: Option<bool>) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        if let Some(view) = server.views.iter_mut().find(|view| view.id == view_id) {
+            let insets = view.decorations.insets();
+            view.decorations.preferred = match server_side {
+                Some(true) => Some(XdgToplevelDecorationMode::ServerSide),
+                Some(false) => Some(XdgToplevelDecorationMode::ClientSide),
+                None => None
+            };
+            update_decorations(view, insets)
+                .unwrap_or_else(|_| warn!("Could not set decoration mode, view was destroyed"))
+        }
+    });
+}
+
+/// Set the size of the titlebar on the side of the view with the id.
+pub fn set_titlebar_size(view_id: usize, side: TitlebarSide, size: i32) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        if let Some(view) = server.views.iter_mut().find(|view| view.id == view_id) {
+            let insets = view.decorations.insets();
+            view.decorations.titlebars[side as usize] = size.max(0);
+            fit_surface(view, insets)
+                .unwrap_or_else(|_| warn!("Could not resize view, it was destroyed"));
+        }
+    });
+}
+
+/// Set the width of the border of the view with the id.
+pub fn set_border_width(view_id: usize, width: i32) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        if let Some(view) = server.views.iter_mut().find(|view| view.id == view_id) {
+            view.decorations.border_width = width.max(0);
+        }
+    });
+}
+
+/// Set the color of the border of the view with the id, as RGBA.
+pub fn set_border_color(view_id: usize, color: [u8; 4]) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        if let Some(view) = server.views.iter_mut().find(|view| view.id == view_id) {
+            view.decorations.border_color = color;
+        }
+    });
+}
+
+/// Get the view whose titlebars or border are at the output layout
+/// coordinates.
+///
+/// Views above hide the decorations of the views below them.
+pub fn decorated_view_at(views: &mut [View], lx: f64, ly: f64) -> HandleResult<Option<usize>> {
+    for view in views.iter_mut().rev() {
+        if view.surface_at(lx, ly)?.is_some() {
+            return Ok(None)
+        }
+        if view.contains(lx, ly)? {
+            return Ok(Some(view.id))
+        }
+    }
+    Ok(None)
+}
+
+/// Get the titlebar at the output layout coordinates, along with the id of
+/// its view.
+///
+/// Views above the titlebar hide it. The coordinates returned are local to
+/// the titlebar.
+pub fn titlebar_at(views: &mut [View],
+                   lx: f64,
+                   ly: f64)
+                   -> HandleResult<Option<(usize, TitlebarSide, f64, f64)>> {
+    for view in views.iter_mut().rev() {
+        if view.surface_at(lx, ly)?.is_some() {
+            return Ok(None)
+        }
+        if view.contains(lx, ly)? {
+            let titlebar = view.titlebar_at(lx, ly)?;
+            return Ok(titlebar.map(|(side, tx, ty)| (view.id, side, tx, ty)))
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn server_side(titlebars: [i32; 4], border_width: i32) -> Decorations {
+        Decorations { preferred: Some(XdgToplevelDecorationMode::ServerSide),
+                      titlebars,
+                      border_width,
+                      ..Decorations::default() }
+    }
+
+    #[test]
+    fn titlebars_larger_than_client_are_cut_off() {
+        let decorations = server_side([30, 30, 30, 30], 0);
+        let size = Size::new(20, 10);
+        for &side in TitlebarSide::all().iter() {
+            let Area { origin, size: area_size } = decorations.titlebar_area(side, size);
+            assert!(area_size.width >= 0 && area_size.height >= 0);
+            assert!(origin.x >= 0 && origin.x + area_size.width <= size.width);
+            assert!(origin.y >= 0 && origin.y + area_size.height <= size.height);
+        }
+    }
+
+    #[test]
+    fn border_wider_than_client_is_not_negative() {
+        let decorations = server_side([0; 4], 20);
+        for &size in [Size::new(10, 10), Size::new(0, 0), Size::new(-5, 3)].iter() {
+            for area in decorations.border_areas(size).iter() {
+                assert!(area.size.width >= 0 && area.size.height >= 0);
+            }
+        }
+    }
+}
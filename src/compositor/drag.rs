@@ -0,0 +1,201 @@
+//! Drag and drop between clients.
+//!
+//! While a drag is in progress the pointer only moves the drag, which is
+//! offered to the surface under the cursor. Its icon, if it has one, follows
+//! the cursor.
+
+use awesome;
+use compositor::{self, CursorTheme, Seat, Server, View};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use wlroots::{self, project_box, Area, Compositor, CursorHandle, DragHandle, DragIconHandle,
+              HandleResult, KeyboardHandle, Origin, OutputLayoutHandle, Renderer, Size,
+              SurfaceHandle};
+
+/// A drag that is in progress.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drag {
+    pub handle: DragHandle,
+    /// The surface drawn at the cursor while dragging.
+    pub icon: Option<DragIconHandle>,
+    /// The surface the drag was last offered to.
+    pub focus: Option<SurfaceHandle>
+}
+
+/// Start the drag, taking over the pointer until it is dropped.
+pub fn drag_started(compositor: &mut Compositor, drag: &mut wlroots::Drag) {
+    let server: &mut Server = compositor.into();
+    let Server { ref mut seat,
+                 ref mut views,
+                 ref mut cursor,
+                 ref lock,
+                 .. } = *server;
+    // A drag can't be started while the session is locked or moving a view.
+    if lock.locked || seat.action.is_some() {
+        drag.cancel();
+        return
+    }
+    seat.drag = Some(Drag { handle: drag.weak_reference(),
+                            icon: drag.icon(),
+                            focus: None });
+    run_handles!([(seat: {&mut seat.seat})] => {
+        seat.pointer_clear_focus();
+    }).expect("Seat was destroyed");
+    let time = SystemTime::now().duration_since(UNIX_EPOCH)
+                                .expect("Time went backwards");
+    drag_motion(seat, views, cursor, time).expect("Could not send drag motion")
+}
+
+/// Forget the drag once it's over, and give the pointer back to the surface
+/// under it.
+pub fn drag_destroyed(compositor: &mut Compositor, drag: &mut wlroots::Drag) {
+    let server: &mut Server = compositor.into();
+    let Server { ref mut seat,
+                 ref mut views,
+                 ref mut cursor,
+                 ref mut cursor_theme,
+                 ref mut keyboards,
+                 ref lock,
+                 .. } = *server;
+    let weak_reference = drag.weak_reference();
+    match seat.drag {
+        Some(ref drag) if drag.handle == weak_reference => {}
+        _ => return
+    }
+    seat.drag = None;
+    if lock.locked {
+        return
+    }
+    let time = SystemTime::now().duration_since(UNIX_EPOCH)
+                                .expect("Time went backwards");
+    compositor::update_hovered(seat,
+                               &mut **keyboards,
+                               views,
+                               cursor,
+                               cursor_theme,
+                               time,
+                               true).expect("Could not update the view under the pointer")
+}
+
+/// Offer the drag to the surface under the cursor.
+///
+/// The surface the drag was over is left if it's no longer under the cursor.
+pub fn drag_motion(seat: &mut Seat,
+                   views: &mut [View],
+                   cursor: &mut CursorHandle,
+                   time: Duration)
+                   -> HandleResult<()> {
+    let drag = match seat.drag {
+        Some(ref mut drag) => drag,
+        None => return Ok(())
+    };
+    let (lx, ly) = run_handles!([(cursor: {cursor})] => {
+        cursor.coords()
+    })?;
+    // Drawins can't take drops, but they still shadow the views.
+    let under_cursor = if awesome::over_drawin(lx, ly) {
+        None
+    } else {
+        compositor::view_at(views, lx, ly).map(|(_, surface, sx, sy)| (surface, sx, sy))
+    };
+    let surface = under_cursor.as_ref().map(|&(ref surface, ..)| surface.clone());
+    if drag.focus != surface {
+        if drag.focus.take().is_some() {
+            run_handles!([(handle: {&mut drag.handle})] => {
+                handle.send_leave();
+            })?;
+        }
+        if let Some((surface, sx, sy)) = under_cursor.clone() {
+            run_handles!([(handle: {&mut drag.handle}),
+                          (surface: {surface})] => {
+                handle.send_enter(surface, sx, sy);
+            })?;
+        }
+        drag.focus = surface;
+    }
+    match under_cursor {
+        Some((_, sx, sy)) => {
+            run_handles!([(handle: {&mut drag.handle})] => {
+                handle.send_motion(time, sx, sy);
+            })
+        }
+        None => Ok(())
+    }
+}
+
+/// Drop the drag on the surface under the cursor, and give the pointer back
+/// to that surface.
+///
+/// If there is no surface there the drag is cancelled instead.
+pub fn drop_drag(seat: &mut Seat,
+                 keyboards: &mut [KeyboardHandle],
+                 views: &mut [View],
+                 cursor: &mut CursorHandle,
+                 cursor_theme: &mut CursorTheme,
+                 time: Duration)
+                 -> HandleResult<()> {
+    let mut drag = match seat.drag.take() {
+        Some(drag) => drag,
+        None => return Ok(())
+    };
+    run_handles!([(handle: {&mut drag.handle})] => {
+        if drag.focus.is_some() {
+            handle.send_drop(time);
+        } else {
+            handle.cancel();
+        }
+    })?;
+    compositor::update_hovered(seat, keyboards, views, cursor, cursor_theme, time, true)
+}
+
+/// Cancel the drag, e.g because its source went away.
+///
+/// The pointer isn't given back to the surface under it, as that depends on
+/// why the drag was cancelled.
+pub fn cancel_drag(seat: &mut Seat) -> HandleResult<()> {
+    let mut drag = match seat.drag.take() {
+        Some(drag) => drag,
+        None => return Ok(())
+    };
+    run_handles!([(handle: {&mut drag.handle})] => {
+        if drag.focus.is_some() {
+            handle.send_leave();
+        }
+        handle.cancel();
+    })
+}
+
+/// Draw the icon of the drag at the cursor.
+pub fn render_drag_icon(renderer: &mut Renderer,
+                        layout: &mut OutputLayoutHandle,
+                        cursor: &mut CursorHandle,
+                        seat: &mut Seat)
+                        -> HandleResult<()> {
+    let icon = match seat.drag.as_mut().and_then(|drag| drag.icon.as_mut()) {
+        Some(icon) => icon,
+        None => return Ok(())
+    };
+    let (lx, ly) = run_handles!([(cursor: {cursor})] => {
+        cursor.coords()
+    })?;
+    let (surface, (dx, dy)) = run_handles!([(icon: {icon})] => {
+        (icon.surface(), icon.offset())
+    })?;
+    run_handles!([(surface: {surface}),
+                  (layout: {layout})] => {
+        let (width, height) = surface.current_state().size();
+        let scale = renderer.output.scale() as i32;
+        let origin = Origin::new(lx as i32 + dx, ly as i32 + dy);
+        let render_box = Area::new(origin, Size::new(width * scale, height * scale));
+        if layout.intersects(renderer.output, render_box) {
+            let transform = renderer.output.get_transform().invert();
+            let matrix = project_box(render_box,
+                                     transform,
+                                     0.0,
+                                     renderer.output.transform_matrix());
+            renderer.render_texture_with_matrix(&surface.texture(), matrix);
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)
+                                       .expect("Time went backwards");
+            surface.send_frame_done(now);
+        }
+    })
+}
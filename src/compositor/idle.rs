@@ -0,0 +1,127 @@
+//! Tracking how long the user has been idle.
+//!
+//! Clients (e.g screen lockers) learn about it through the idle protocol,
+//! and Lua through the `idle::start` and `idle::stop` signals. Clients can
+//! also inhibit idleness (e.g video players) through the idle inhibit
+//! protocol.
+
+use awesome;
+use compositor::{self, Seat, Server};
+use std::time::Instant;
+use wlroots::{Compositor, IdleHandle, IdleInhibitHandler, IdleInhibitor, IdleInhibitorHandle};
+
+/// How long the user has been idle, and who is keeping them from being idle.
+#[derive(Debug)]
+pub struct IdleState {
+    pub idle: IdleHandle,
+    /// Clients that don't want the user to become idle.
+    pub inhibitors: Vec<IdleInhibitorHandle>,
+    last_activity: Instant,
+    timeouts: Vec<IdleTimeout>
+}
+
+/// A timeout Lua wants to know about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IdleTimeout {
+    seconds: u64,
+    /// If the user has been idle for longer than the timeout.
+    idle: bool
+}
+
+#[derive(Debug, Default)]
+pub struct IdleInhibitManager;
+
+impl Default for IdleState {
+    fn default() -> Self {
+        IdleState { idle: IdleHandle::default(),
+                    inhibitors: Vec::default(),
+                    last_activity: Instant::now(),
+                    timeouts: Vec::default() }
+    }
+}
+
+impl IdleInhibitManager {
+    pub fn new() -> Self {
+        IdleInhibitManager::default()
+    }
+}
+
+impl IdleInhibitHandler for IdleInhibitManager {
+    fn inhibitor_created(&mut self, compositor: &mut Compositor, inhibitor: &mut IdleInhibitor) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut idle,
+                     ref mut seat,
+                     .. } = *server;
+        idle.inhibitors.push(inhibitor.weak_reference());
+        notify_activity(idle, seat);
+        run_handles!([(idle_handle: {&mut idle.idle}), (seat: {&mut seat.seat})] => {
+            idle_handle.set_enabled(seat, false);
+        }).expect("Could not inhibit idle");
+    }
+
+    fn inhibitor_destroyed(&mut self, compositor: &mut Compositor, inhibitor: &mut IdleInhibitor) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut idle,
+                     ref mut seat,
+                     .. } = *server;
+        let weak_reference = inhibitor.weak_reference();
+        idle.inhibitors.retain(|i| *i != weak_reference);
+        if idle.inhibitors.is_empty() {
+            // Only start counting once the client stopped inhibiting.
+            idle.last_activity = Instant::now();
+            run_handles!([(idle_handle: {&mut idle.idle}), (seat: {&mut seat.seat})] => {
+                idle_handle.set_enabled(seat, true);
+            }).expect("Could not uninhibit idle");
+        }
+    }
+}
+
+/// Let clients and Lua know the user did something, so they are no longer
+/// idle.
+///
+/// This must be called for every input event.
+pub fn notify_activity(idle: &mut IdleState, seat: &mut Seat) {
+    idle.last_activity = Instant::now();
+    run_handles!([(idle_handle: {&mut idle.idle}), (seat: {&mut seat.seat})] => {
+        idle_handle.notify_activity(seat);
+    }).expect("Could not notify idle activity");
+    for timeout in idle.timeouts.iter_mut().filter(|timeout| timeout.idle) {
+        timeout.idle = false;
+        awesome::idle_stop(timeout.seconds);
+    }
+}
+
+/// Emit `idle::start` for the timeouts that have passed since the last
+/// activity.
+///
+/// This is called every second by the compositor.
+pub fn check_idle(compositor: &mut Compositor) {
+    let server: &mut Server = compositor.into();
+    let idle = &mut server.idle;
+    if !idle.inhibitors.is_empty() {
+        return
+    }
+    let elapsed = idle.last_activity.elapsed().as_secs();
+    for timeout in idle.timeouts.iter_mut() {
+        if !timeout.idle && elapsed >= timeout.seconds {
+            timeout.idle = true;
+            awesome::idle_start(timeout.seconds);
+        }
+    }
+}
+
+/// Set the timeouts, in seconds, after which Lua is told the user is idle.
+///
+/// Timeouts that were replaced while the user was idle get `idle::stop`.
+pub fn set_idle_timeouts(timeouts: Vec<u64>) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        let idle = &mut server.idle;
+        for timeout in idle.timeouts.drain(..).filter(|timeout| timeout.idle) {
+            awesome::idle_stop(timeout.seconds);
+        }
+        idle.timeouts = timeouts.into_iter()
+                                .map(|seconds| IdleTimeout { seconds, idle: false })
+                                .collect();
+    });
+}
@@ -0,0 +1,152 @@
+//! Pointer constraints, which let clients (e.g games) lock the pointer in
+//! place or confine it to a region of their surface.
+//!
+//! A constraint is only active while its surface has keyboard focus. A
+//! constraint that confines the pointer waits for the pointer to enter its
+//! region before it becomes active, as the pointer couldn't move otherwise.
+
+use compositor::{self, Seat, Server};
+use wlroots::{Compositor, ConstraintType, HandleResult, Origin, PointerConstraint,
+              PointerConstraintsHandler};
+
+#[derive(Debug, Default)]
+pub struct PointerConstraintsManager;
+
+impl PointerConstraintsManager {
+    pub fn new() -> Self {
+        PointerConstraintsManager::default()
+    }
+}
+
+impl PointerConstraintsHandler for PointerConstraintsManager {
+    fn constraint_created(&mut self,
+                          compositor: &mut Compositor,
+                          constraint: &mut PointerConstraint) {
+        let server: &mut Server = compositor.into();
+        server.seat.constraints.push(constraint.weak_reference());
+        update_constraint(&mut server.seat).expect("Could not activate pointer constraint");
+    }
+
+    fn constraint_destroyed(&mut self,
+                            compositor: &mut Compositor,
+                            constraint: &mut PointerConstraint) {
+        let server: &mut Server = compositor.into();
+        let weak_reference = constraint.weak_reference();
+        server.seat.constraints.retain(|c| *c != weak_reference);
+        if server.seat.active_constraint.as_ref() == Some(&weak_reference) {
+            server.seat.active_constraint = None;
+        }
+        if server.seat.pending_constraint.as_ref() == Some(&weak_reference) {
+            server.seat.pending_constraint = None;
+        }
+    }
+}
+
+/// Activate the constraint of the focused surface, if it has one, and
+/// deactivate any other constraint.
+pub fn update_constraint(seat: &mut Seat) -> HandleResult<()> {
+    let focused = seat.focused.as_mut().map(|view| view.shell.surface());
+    let mut wanted = None;
+    for constraint in &mut seat.constraints {
+        let surface = run_handles!([(constraint: {&mut *constraint})] => {
+            constraint.surface()
+        })?;
+        if Some(surface) == focused {
+            wanted = Some(constraint.clone());
+            break
+        }
+    }
+    if seat.active_constraint == wanted {
+        return Ok(())
+    }
+    seat.pending_constraint = None;
+    if let Some(mut active) = seat.active_constraint.take() {
+        // It may have been destroyed along with its surface.
+        run_handles!([(active: {&mut active})] => {
+            active.send_deactivated();
+        }).ok();
+    }
+    if let Some(mut wanted) = wanted {
+        let confined = run_handles!([(constraint: {&mut wanted})] => {
+            match constraint.constraint_type() {
+                ConstraintType::Locked => {
+                    constraint.send_activated();
+                    false
+                }
+                ConstraintType::Confined => true
+            }
+        })?;
+        if confined {
+            seat.pending_constraint = Some(wanted);
+        } else {
+            seat.active_constraint = Some(wanted);
+        }
+    }
+    Ok(())
+}
+
+/// Limit how far the pointer can move by the active constraint.
+///
+/// If the pointer would leave the region it is confined to, it slides along
+/// the edge of the region instead. A constraint waiting for the pointer to
+/// enter its region is activated once it has.
+pub fn constrain_motion(seat: &mut Seat,
+                        (lx, ly): (f64, f64),
+                        (dx, dy): (f64, f64))
+                        -> HandleResult<(f64, f64)> {
+    let Origin { x, y } = match seat.focused {
+        Some(ref view) => view.surface_origin(),
+        None => return Ok((dx, dy))
+    };
+    let (sx, sy) = (lx - x as f64, ly - y as f64);
+    if let Some(mut pending) = seat.pending_constraint.take() {
+        let entered = run_handles!([(constraint: {&mut pending})] => {
+            let entered = constraint.region_contains(sx, sy);
+            if entered {
+                constraint.send_activated();
+            }
+            entered
+        })?;
+        if entered {
+            seat.active_constraint = Some(pending);
+        } else {
+            seat.pending_constraint = Some(pending);
+        }
+    }
+    let constraint = match seat.active_constraint.as_mut() {
+        Some(constraint) => constraint,
+        None => return Ok((dx, dy))
+    };
+    run_handles!([(constraint: {constraint})] => {
+        match constraint.constraint_type() {
+            ConstraintType::Locked => (0.0, 0.0),
+            ConstraintType::Confined => {
+                if constraint.region_contains(sx + dx, sy + dy) {
+                    (dx, dy)
+                } else if constraint.region_contains(sx + dx, sy) {
+                    (dx, 0.0)
+                } else if constraint.region_contains(sx, sy + dy) {
+                    (0.0, dy)
+                } else {
+                    (0.0, 0.0)
+                }
+            }
+        }
+    })
+}
+
+/// Deactivate the active pointer constraint, giving the pointer back to the
+/// user.
+///
+/// It is activated again once its surface regains focus.
+pub fn release_pointer_constraint() {
+    compositor::run_later(|compositor| {
+                              let server: &mut Server = compositor.into();
+                              server.seat.pending_constraint = None;
+                              if let Some(mut active) = server.seat.active_constraint.take() {
+                                  run_handles!([(active: {&mut active})] => {
+                                      active.send_deactivated();
+                                  }).ok();
+                              }
+                          });
+}
@@ -1,6 +1,8 @@
-use compositor::{self, Server};
+use awesome;
+use compositor::{self, InputType, Server};
 use wlroots::{Capability, Compositor, InputManagerHandler, Keyboard, KeyboardHandler, Pointer,
-              PointerHandler};
+              PointerHandler, TabletPad, TabletPadHandler, TabletTool, TabletToolHandler, Touch,
+              TouchHandler};
 
 pub struct InputManager;
 
@@ -16,6 +18,15 @@ impl InputManagerHandler for InputManager {
                       keyboard: &mut Keyboard)
                       -> Option<Box<KeyboardHandler>> {
         let server: &mut Server = compositor.into();
+        compositor::set_keymap(&server.xkb_config, keyboard);
+        keyboard.set_repeat_info(server.repeat_info.rate, server.repeat_info.delay);
+        let config = compositor::configure_device(&server.input_rules,
+                                                  keyboard.input_device(),
+                                                  InputType::Keyboard);
+        awesome::add_input(compositor::device_id(keyboard.input_device()),
+                           keyboard.input_device().name(),
+                           InputType::Keyboard,
+                           config);
         server.keyboards.push(keyboard.weak_reference());
         if server.keyboards.len() == 1 {
             // Now that we have at least one keyboard, update the seat capabilities.
@@ -34,6 +45,13 @@ impl InputManagerHandler for InputManager {
                      pointer: &mut Pointer)
                      -> Option<Box<PointerHandler>> {
         let server: &mut Server = compositor.into();
+        let input_type = compositor::input_type(pointer.input_device(), InputType::Pointer);
+        let config =
+            compositor::configure_device(&server.input_rules, pointer.input_device(), input_type);
+        awesome::add_input(compositor::device_id(pointer.input_device()),
+                           pointer.input_device().name(),
+                           input_type,
+                           config);
         server.pointers.push(pointer.weak_reference());
         if server.pointers.len() == 1 {
             // Now that we have at least one keyboard, update the seat capabilities.
@@ -50,7 +68,97 @@ impl InputManagerHandler for InputManager {
         Some(Box::new(compositor::Pointer))
     }
 
+    fn touch_added(&mut self,
+                   compositor: &mut Compositor,
+                   touch: &mut Touch)
+                   -> Option<Box<TouchHandler>> {
+        let server: &mut Server = compositor.into();
+        let config = compositor::configure_device(&server.input_rules,
+                                                  touch.input_device(),
+                                                  InputType::Touch);
+        awesome::add_input(compositor::device_id(touch.input_device()),
+                           touch.input_device().name(),
+                           InputType::Touch,
+                           config.clone());
+        server.touches.push(touch.weak_reference());
+        if server.touches.len() == 1 {
+            run_handles!([(seat: {&mut server.seat.seat})] => {
+                let mut capabilities = seat.capabilities();
+                capabilities.insert(Capability::Touch);
+                seat.set_capabilities(capabilities);
+            }).expect("Seat was destroyed");
+        }
+        // The cursor maps the touch points to the output layout.
+        run_handles!([(cursor: {&mut server.cursor})] => {
+            cursor.attach_input_device(touch.input_device());
+        }).expect("Cursor was destroyed");
+        compositor::map_to_output(&mut server.cursor,
+                                  &mut server.layout,
+                                  touch.input_device(),
+                                  config.output.as_ref().map(String::as_str))
+            .expect("Could not map touch to output");
+        Some(Box::new(compositor::Touch))
+    }
+
+    fn tablet_tool_added(&mut self,
+                         compositor: &mut Compositor,
+                         tablet: &mut TabletTool)
+                         -> Option<Box<TabletToolHandler>> {
+        let server: &mut Server = compositor.into();
+        let config = compositor::configure_device(&server.input_rules,
+                                                  tablet.input_device(),
+                                                  InputType::Tablet);
+        awesome::add_input(compositor::device_id(tablet.input_device()),
+                           tablet.input_device().name(),
+                           InputType::Tablet,
+                           config.clone());
+        let tablet_v2 = run_handles!([(tablet_manager: {&mut server.tablet_manager}),
+                                      (seat: {&mut server.seat.seat})] => {
+            tablet_manager.create_tablet(seat, tablet.input_device())
+        }).expect("Could not advertise tablet");
+        server.tablets.push(compositor::Tablet { handle: tablet.weak_reference(),
+                                                 tablet_v2,
+                                                 tools: Vec::new() });
+        compositor::attach_pads(&mut server.pads, tablet);
+        // The cursor maps the tools to the output layout, and moves with
+        // them for clients that don't know about tablets.
+        run_handles!([(cursor: {&mut server.cursor})] => {
+            cursor.attach_input_device(tablet.input_device());
+        }).expect("Cursor was destroyed");
+        compositor::map_to_output(&mut server.cursor,
+                                  &mut server.layout,
+                                  tablet.input_device(),
+                                  config.output.as_ref().map(String::as_str))
+            .expect("Could not map tablet to output");
+        Some(Box::new(compositor::TabletHandler))
+    }
+
+    fn tablet_pad_added(&mut self,
+                        compositor: &mut Compositor,
+                        pad: &mut TabletPad)
+                        -> Option<Box<TabletPadHandler>> {
+        let server: &mut Server = compositor.into();
+        let config = compositor::configure_device(&server.input_rules,
+                                                  pad.input_device(),
+                                                  InputType::TabletPad);
+        awesome::add_input(compositor::device_id(pad.input_device()),
+                           pad.input_device().name(),
+                           InputType::TabletPad,
+                           config);
+        let pad_v2 = run_handles!([(tablet_manager: {&mut server.tablet_manager}),
+                                   (seat: {&mut server.seat.seat})] => {
+            tablet_manager.create_pad(seat, pad.input_device())
+        }).expect("Could not advertise tablet pad");
+        let tablet = compositor::pad_tablet(&mut server.tablets, pad.input_device());
+        server.pads.push(compositor::Pad { handle: pad.weak_reference(),
+                                           pad_v2,
+                                           tablet,
+                                           surface: None });
+        Some(Box::new(compositor::PadHandler))
+    }
+
     fn keyboard_removed(&mut self, compositor: &mut Compositor, keyboard: &mut Keyboard) {
+        awesome::remove_input(compositor::device_id(keyboard.input_device()), InputType::Keyboard);
         let server: &mut Server = compositor.into();
         let weak_reference = keyboard.weak_reference();
         if let Some(index) = server.keyboards.iter().position(|k| *k == weak_reference) {
@@ -66,6 +174,8 @@ impl InputManagerHandler for InputManager {
     }
 
     fn pointer_removed(&mut self, compositor: &mut Compositor, pointer: &mut Pointer) {
+        let input_type = compositor::input_type(pointer.input_device(), InputType::Pointer);
+        awesome::remove_input(compositor::device_id(pointer.input_device()), input_type);
         let server: &mut Server = compositor.into();
         let weak_reference = pointer.weak_reference();
         if let Some(index) = server.pointers.iter().position(|p| *p == weak_reference) {
@@ -84,4 +194,45 @@ impl InputManagerHandler for InputManager {
             cursor.deattach_input_device(pointer.input_device());
         }).expect("Cursor was destroyed");
     }
+
+    fn touch_removed(&mut self, compositor: &mut Compositor, touch: &mut Touch) {
+        awesome::remove_input(compositor::device_id(touch.input_device()), InputType::Touch);
+        let server: &mut Server = compositor.into();
+        let weak_reference = touch.weak_reference();
+        if let Some(index) = server.touches.iter().position(|t| *t == weak_reference) {
+            server.touches.remove(index);
+            if server.touches.len() == 0 {
+                run_handles!([(seat: {&mut server.seat.seat})] => {
+                    let mut capabilities = seat.capabilities();
+                    capabilities.remove(Capability::Touch);
+                    seat.set_capabilities(capabilities);
+                }).expect("Seat was destroyed")
+            }
+        }
+        run_handles!([(cursor: {&mut server.cursor})] => {
+            cursor.deattach_input_device(touch.input_device());
+        }).expect("Cursor was destroyed");
+    }
+
+    fn tablet_tool_removed(&mut self, compositor: &mut Compositor, tablet: &mut TabletTool) {
+        awesome::remove_input(compositor::device_id(tablet.input_device()), InputType::Tablet);
+        let server: &mut Server = compositor.into();
+        let weak_reference = tablet.weak_reference();
+        server.tablets.retain(|t| t.handle != weak_reference);
+        for pad in &mut server.pads {
+            if pad.tablet.as_ref() == Some(&weak_reference) {
+                pad.tablet = None;
+            }
+        }
+        run_handles!([(cursor: {&mut server.cursor})] => {
+            cursor.deattach_input_device(tablet.input_device());
+        }).expect("Cursor was destroyed");
+    }
+
+    fn tablet_pad_removed(&mut self, compositor: &mut Compositor, pad: &mut TabletPad) {
+        awesome::remove_input(compositor::device_id(pad.input_device()), InputType::TabletPad);
+        let server: &mut Server = compositor.into();
+        let weak_reference = pad.weak_reference();
+        server.pads.retain(|p| p.handle != weak_reference);
+    }
 }
@@ -1,6 +1,98 @@
-use compositor::Server;
-use wlroots::{self, Compositor, KeyboardHandler, key_events::KeyEvent,
-              xkbcommon::xkb::{KEY_Escape, KEY_F1}, WLR_KEY_PRESSED};
+use awesome;
+use compositor::{self, Server};
+use ipc::ModifierMap;
+use std::env;
+use wlroots::{self, Compositor, KeyboardHandle, KeyboardHandler, key_events::KeyEvent,
+              xkbcommon::xkb::{self, KEY_Escape, KEY_F1}, WLR_KEY_PRESSED};
+
+/// The number of real modifiers, which always come first in a keymap.
+const REAL_MODS_COUNT: u32 = 8;
+const DEFAULT_LAYOUT: &'static str = "us";
+const DEFAULT_REPEAT_RATE: i32 = 25;
+const DEFAULT_REPEAT_DELAY: i32 = 600;
+
+/// How keys repeat when they are held down.
+///
+/// Clients are told about this through `wl_keyboard.repeat_info` and do
+/// the repeating themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepeatInfo {
+    /// How many times a second a held key repeats.
+    pub rate: i32,
+    /// How many milliseconds a key has to be held before it repeats.
+    pub delay: i32
+}
+
+impl Default for RepeatInfo {
+    fn default() -> Self {
+        RepeatInfo { rate: DEFAULT_REPEAT_RATE,
+                     delay: DEFAULT_REPEAT_DELAY }
+    }
+}
+
+/// The xkb names used to compile the keymap of every keyboard.
+///
+/// Empty names are filled in by xkbcommon, which respects the
+/// `XKB_DEFAULT_*` environment variables.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct XkbConfig {
+    pub rules: String,
+    pub model: String,
+    pub layout: String,
+    pub variant: String,
+    pub options: Option<String>
+}
+
+impl XkbConfig {
+    /// Compile the keymap these names describe.
+    pub fn keymap(&self) -> Option<xkb::Keymap> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        xkb::Keymap::new_from_names(&context,
+                                    &self.rules,
+                                    &self.model,
+                                    &self.layout,
+                                    &self.variant,
+                                    self.options.clone(),
+                                    xkb::KEYMAP_COMPILE_NO_FLAGS)
+    }
+
+    /// Describe the layouts in the form Lua expects, e.g
+    /// "pc+us+de:2+inet(evdev)".
+    pub fn symbols(&self) -> String {
+        let layout = default_name(&self.layout, "XKB_DEFAULT_LAYOUT")
+            .unwrap_or_else(|| DEFAULT_LAYOUT.into());
+        let variant = default_name(&self.variant, "XKB_DEFAULT_VARIANT").unwrap_or_default();
+        let mut variants = variant.split(',');
+        let mut symbols = "pc".to_string();
+        for (index, layout) in layout.split(',').enumerate() {
+            symbols.push('+');
+            symbols.push_str(layout);
+            match variants.next() {
+                Some(variant) if !variant.is_empty() => {
+                    symbols.push_str(&format!("({})", variant))
+                }
+                _ => {}
+            }
+            if index > 0 {
+                symbols.push_str(&format!(":{}", index + 1));
+            }
+        }
+        symbols.push_str("+inet(evdev)");
+        symbols
+    }
+}
+
+fn default_name(name: &str, var: &str) -> Option<String> {
+    if !name.is_empty() {
+        return Some(name.into())
+    }
+    match env::var(var) {
+        Ok(ref name) if name.is_empty() => None,
+        Ok(name) => Some(name),
+        Err(_) => None
+    }
+}
+
 pub struct Keyboard;
 
 impl KeyboardHandler for Keyboard {
@@ -8,7 +100,33 @@ impl KeyboardHandler for Keyboard {
               compositor: &mut Compositor,
               keyboard: &mut wlroots::Keyboard,
               event: &mut KeyEvent) {
-        if event.key_state() == WLR_KEY_PRESSED {
+        let locked = {
+            let server: &mut Server = compositor.into();
+            server.lock.locked
+        };
+        let pressed = event.key_state() == WLR_KEY_PRESSED;
+        let mods = {
+            let modifiers = keyboard.get_modifier_masks();
+            modifiers.depressed | modifiers.latched | modifiers.locked
+        };
+        // Key bindings are suspended while the session is locked, keys only
+        // go to the lock surface. Keys that are grabbed or trigger a binding
+        // don't go to the focused client.
+        let mut grabbed = false;
+        if !locked {
+            if awesome::keygrabber_running() {
+                for key in event.pressed_keys() {
+                    awesome::keygrabber_handle(mods, key, event.key_state())
+                        .unwrap_or_else(|err| warn!("Could not run keygrabber: {}", err));
+                }
+                grabbed = true;
+            } else {
+                for key in event.pressed_keys() {
+                    grabbed |= awesome::root_key(key, mods, pressed);
+                }
+            }
+        }
+        if pressed && !locked && !grabbed {
             for key in event.pressed_keys() {
                 if key == KEY_Escape {
                     compositor.terminate();
@@ -23,9 +141,147 @@ impl KeyboardHandler for Keyboard {
             }
         }
         let server: &mut Server = compositor.into();
+        compositor::notify_activity(&mut server.idle, &mut server.seat);
         run_handles!([(seat: {&mut server.seat.seat})] => {
-            seat.keyboard_notify_key(event.time_msec(), event.keycode(), event.key_state() as u32);
+            if !grabbed {
+                seat.keyboard_notify_key(event.time_msec(),
+                                         event.keycode(),
+                                         event.key_state() as u32);
+            }
             seat.keyboard_send_modifiers(&mut keyboard.get_modifier_masks());
         }).expect("Seat was destroyed");
+        update_group(keyboard.get_modifier_masks().group);
+    }
+}
+
+/// Get the mask of the modifiers that are active on the keyboards.
+pub fn active_modifiers(keyboards: &mut [KeyboardHandle]) -> u32 {
+    keyboards.iter_mut()
+             .filter_map(|keyboard| {
+                             run_handles!([(keyboard: {keyboard})] => {
+                                 let modifiers = keyboard.get_modifier_masks();
+                                 modifiers.depressed | modifiers.latched | modifiers.locked
+                             }).ok()
+                         })
+             .fold(0, |mods, keyboard_mods| mods | keyboard_mods)
+}
+
+/// Compile the keymap from the configured names and give it to the keyboard.
+pub fn set_keymap(config: &XkbConfig, keyboard: &mut wlroots::Keyboard) {
+    match config.keymap() {
+        Some(keymap) => {
+            keyboard.set_keymap(&keymap);
+            update_modifier_map(&keymap);
+            awesome::XKB.lock().expect("Xkb was poisoned").symbols = config.symbols();
+        }
+        None => warn!("Could not compile a keymap from {:?}", config)
+    }
+}
+
+/// Set the xkb names of the keymap used by every current and future
+/// keyboard.
+pub fn set_xkb_config(config: XkbConfig) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        let keymap = match config.keymap() {
+            Some(keymap) => keymap,
+            None => {
+                warn!("Could not compile a keymap from {:?}", config);
+                return
+            }
+        };
+        for keyboard in &mut server.keyboards {
+            run_handles!([(keyboard: {keyboard})] => {
+                keyboard.set_keymap(&keymap);
+            }).expect("Keyboard was destroyed");
+        }
+        update_modifier_map(&keymap);
+        {
+            let mut xkb = awesome::XKB.lock().expect("Xkb was poisoned");
+            xkb.symbols = config.symbols();
+            // A new keymap starts in the first group.
+            xkb.group = 0;
+        }
+        server.xkb_config = config;
+        awesome::xkb_map_changed();
+    });
+}
+
+/// Set how keys repeat on every current and future keyboard.
+pub fn set_repeat_info(repeat_info: RepeatInfo) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        server.repeat_info = repeat_info;
+        for keyboard in &mut server.keyboards {
+            run_handles!([(keyboard: {keyboard})] => {
+                keyboard.set_repeat_info(repeat_info.rate, repeat_info.delay);
+            }).expect("Keyboard was destroyed");
+        }
+    });
+}
+
+/// Switch every keyboard to the layout group.
+pub fn set_layout_group(group: u32) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut seat,
+                     ref mut keyboards,
+                     .. } = *server;
+        for keyboard in keyboards {
+            run_handles!([(keyboard: {keyboard}),
+                          (seat: {&mut seat.seat})] => {
+                let modifiers = keyboard.get_modifier_masks();
+                keyboard.notify_modifiers(modifiers.depressed,
+                                          modifiers.latched,
+                                          modifiers.locked,
+                                          group);
+                seat.keyboard_send_modifiers(&mut keyboard.get_modifier_masks());
+            }).expect("Keyboard was destroyed");
+        }
+        update_group(group);
+    });
+}
+
+/// Let Lua know if the active layout group changed.
+fn update_group(group: u32) {
+    {
+        let mut xkb = awesome::XKB.lock().expect("Xkb was poisoned");
+        if xkb.group == group {
+            return
+        }
+        xkb.group = group;
+    }
+    awesome::xkb_group_changed();
+}
+
+/// Let Lua know about the modifiers of the keymap, so that it can name them
+/// and match bindings against them.
+pub fn update_modifier_map(keymap: &xkb::Keymap) {
+    *awesome::MODIFIERS.lock().expect("Modifiers was poisoned") = modifier_map(keymap);
+}
+
+fn modifier_map(keymap: &xkb::Keymap) -> ModifierMap {
+    let mut map = ModifierMap { real: Vec::new(),
+                                virtual_mods: Vec::new(),
+                                ignored: 0 };
+    // Setting only a virtual modifier in a state resolves it to the real
+    // modifiers it is bound to.
+    let mut state = xkb::State::new(keymap);
+    for index in 0..keymap.num_mods() {
+        let name = keymap.mod_get_name(index).to_string();
+        if index < REAL_MODS_COUNT {
+            map.real.push(name);
+            continue
+        }
+        state.update_mask(1 << index, 0, 0, 0, 0, 0);
+        let mask = state.serialize_mods(xkb::STATE_MODS_EFFECTIVE);
+        // Virtual modifiers that aren't bound can never be active.
+        if mask != 0 {
+            map.virtual_mods.push((name, mask));
+        }
     }
+    map.ignored = ["Lock", "NumLock"].iter()
+                                     .filter_map(|name| map.mask(name))
+                                     .fold(0, |ignored, mask| ignored | mask);
+    map
 }
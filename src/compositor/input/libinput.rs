@@ -0,0 +1,410 @@
+//! Configuration of the libinput devices, e.g tap-to-click on touchpads.
+//!
+//! Devices are configured by a list of rules, where later rules override the
+//! settings of earlier rules that match the same device.
+
+use compositor::{self, Server};
+use std::os::raw::{c_double, c_int};
+use wlroots::{InputDevice, wlroots_sys::wlr_input_device};
+
+const ACCEL_PROFILE_FLAT: c_int = 1 << 0;
+const ACCEL_PROFILE_ADAPTIVE: c_int = 1 << 1;
+const SCROLL_NO_SCROLL: c_int = 0;
+const SCROLL_2FG: c_int = 1 << 0;
+const SCROLL_EDGE: c_int = 1 << 1;
+const SCROLL_ON_BUTTON_DOWN: c_int = 1 << 2;
+const CONFIG_STATUS_SUCCESS: c_int = 0;
+
+#[allow(non_camel_case_types)]
+enum libinput_device {}
+#[allow(non_camel_case_types)]
+enum libinput_device_group {}
+
+extern "C" {
+    fn wlr_input_device_is_libinput(device: *mut wlr_input_device) -> bool;
+    fn wlr_libinput_get_device_handle(device: *mut wlr_input_device) -> *mut libinput_device;
+    fn libinput_device_get_device_group(device: *mut libinput_device)
+                                        -> *mut libinput_device_group;
+    fn libinput_device_config_tap_get_finger_count(device: *mut libinput_device) -> c_int;
+    fn libinput_device_config_tap_set_enabled(device: *mut libinput_device,
+                                              enable: c_int)
+                                              -> c_int;
+    fn libinput_device_config_scroll_set_natural_scroll_enabled(device: *mut libinput_device,
+                                                                enable: c_int)
+                                                                -> c_int;
+    fn libinput_device_config_accel_set_profile(device: *mut libinput_device,
+                                                profile: c_int)
+                                                -> c_int;
+    fn libinput_device_config_accel_set_speed(device: *mut libinput_device,
+                                              speed: c_double)
+                                              -> c_int;
+    fn libinput_device_config_left_handed_set(device: *mut libinput_device,
+                                              left_handed: c_int)
+                                              -> c_int;
+    fn libinput_device_config_dwt_set_enabled(device: *mut libinput_device,
+                                              enable: c_int)
+                                              -> c_int;
+    fn libinput_device_config_scroll_set_method(device: *mut libinput_device,
+                                                method: c_int)
+                                                -> c_int;
+    fn libinput_device_config_middle_emulation_set_enabled(device: *mut libinput_device,
+                                                           enable: c_int)
+                                                           -> c_int;
+}
+
+/// The kind of an input device, as far as configuration is concerned.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum InputType {
+    Keyboard,
+    Pointer,
+    /// A pointer that can be tapped.
+    Touchpad,
+    Touch,
+    Tablet,
+    TabletPad
+}
+
+impl InputType {
+    pub fn from_str(input_type: &str) -> Option<Self> {
+        match input_type {
+            "keyboard" => Some(InputType::Keyboard),
+            "pointer" => Some(InputType::Pointer),
+            "touchpad" => Some(InputType::Touchpad),
+            "touch" => Some(InputType::Touch),
+            "tablet" => Some(InputType::Tablet),
+            "tablet_pad" => Some(InputType::TabletPad),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            InputType::Keyboard => "keyboard",
+            InputType::Pointer => "pointer",
+            InputType::Touchpad => "touchpad",
+            InputType::Touch => "touch",
+            InputType::Tablet => "tablet",
+            InputType::TabletPad => "tablet_pad"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AccelProfile {
+    /// The pointer moves as far as the device does.
+    Flat,
+    /// The pointer moves further the faster the device moves.
+    Adaptive
+}
+
+impl AccelProfile {
+    pub fn from_str(profile: &str) -> Option<Self> {
+        match profile {
+            "flat" => Some(AccelProfile::Flat),
+            "adaptive" => Some(AccelProfile::Adaptive),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            AccelProfile::Flat => "flat",
+            AccelProfile::Adaptive => "adaptive"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ScrollMethod {
+    NoScroll,
+    TwoFinger,
+    Edge,
+    /// Scroll by moving the device while a button is held down.
+    OnButtonDown
+}
+
+impl ScrollMethod {
+    pub fn from_str(method: &str) -> Option<Self> {
+        match method {
+            "none" => Some(ScrollMethod::NoScroll),
+            "two_finger" => Some(ScrollMethod::TwoFinger),
+            "edge" => Some(ScrollMethod::Edge),
+            "on_button_down" => Some(ScrollMethod::OnButtonDown),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ScrollMethod::NoScroll => "none",
+            ScrollMethod::TwoFinger => "two_finger",
+            ScrollMethod::Edge => "edge",
+            ScrollMethod::OnButtonDown => "on_button_down"
+        }
+    }
+}
+
+/// The libinput settings of a device.
+///
+/// Settings that are `None` are left as libinput set them up.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InputConfig {
+    pub tap_to_click: Option<bool>,
+    pub natural_scrolling: Option<bool>,
+    pub accel_profile: Option<AccelProfile>,
+    /// Between -1 (slowest) and 1 (fastest).
+    pub accel_speed: Option<f64>,
+    pub left_handed: Option<bool>,
+    pub disable_while_typing: Option<bool>,
+    pub scroll_method: Option<ScrollMethod>,
+    pub middle_emulation: Option<bool>,
+    /// The name of the output absolute devices (e.g tablets) are mapped to.
+    ///
+    /// This isn't a libinput setting, it is applied by the cursor.
+    pub output: Option<String>
+}
+
+impl InputConfig {
+    /// Override these settings with the ones that are set in `other`.
+    pub fn merge(&mut self, other: &InputConfig) {
+        self.tap_to_click = other.tap_to_click.or(self.tap_to_click);
+        self.natural_scrolling = other.natural_scrolling.or(self.natural_scrolling);
+        self.accel_profile = other.accel_profile.or(self.accel_profile);
+        self.accel_speed = other.accel_speed.or(self.accel_speed);
+        self.left_handed = other.left_handed.or(self.left_handed);
+        self.disable_while_typing = other.disable_while_typing.or(self.disable_while_typing);
+        self.scroll_method = other.scroll_method.or(self.scroll_method);
+        self.middle_emulation = other.middle_emulation.or(self.middle_emulation);
+        self.output = other.output.clone().or(self.output.take());
+    }
+}
+
+/// Which devices an `InputRule` applies to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputMatch {
+    All,
+    Name(String),
+    Type(InputType)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputRule {
+    pub matches: InputMatch,
+    pub config: InputConfig
+}
+
+impl InputRule {
+    pub fn matches(&self, name: &str, input_type: InputType) -> bool {
+        match self.matches {
+            InputMatch::All => true,
+            InputMatch::Name(ref rule_name) => rule_name == name,
+            // Touchpads are pointers too.
+            InputMatch::Type(InputType::Pointer) => {
+                input_type == InputType::Pointer || input_type == InputType::Touchpad
+            }
+            InputMatch::Type(rule_type) => rule_type == input_type
+        }
+    }
+}
+
+/// Get the settings of all the rules that match the device.
+pub fn input_config(rules: &[InputRule], name: &str, input_type: InputType) -> InputConfig {
+    let mut config = InputConfig::default();
+    for rule in rules.iter().filter(|rule| rule.matches(name, input_type)) {
+        config.merge(&rule.config)
+    }
+    config
+}
+
+/// Refine the type of the device, e.g pointers that can be tapped are
+/// touchpads.
+pub fn input_type(device: &InputDevice, input_type: InputType) -> InputType {
+    unsafe {
+        match libinput_handle(device) {
+            Some(handle) if input_type == InputType::Pointer => {
+                if libinput_device_config_tap_get_finger_count(handle) > 0 {
+                    InputType::Touchpad
+                } else {
+                    InputType::Pointer
+                }
+            }
+            _ => input_type
+        }
+    }
+}
+
+/// Apply the settings of the rules that match the device.
+///
+/// Settings the device does not support are skipped. The settings that were
+/// applied are returned.
+pub fn configure_device(rules: &[InputRule],
+                        device: &InputDevice,
+                        input_type: InputType)
+                        -> InputConfig {
+    let name = device.name();
+    let config = input_config(rules, &name, input_type);
+    let handle = match unsafe { libinput_handle(device) } {
+        Some(handle) => handle,
+        None => return config
+    };
+    unsafe {
+        let mut statuses = Vec::new();
+        if let Some(enable) = config.tap_to_click {
+            statuses.push(("tap_to_click",
+                           libinput_device_config_tap_set_enabled(handle, enable as c_int)));
+        }
+        if let Some(enable) = config.natural_scrolling {
+            statuses.push(("natural_scrolling",
+                           libinput_device_config_scroll_set_natural_scroll_enabled(handle,
+                                                                                    enable as
+                                                                                    c_int)));
+        }
+        if let Some(profile) = config.accel_profile {
+            let profile = match profile {
+                AccelProfile::Flat => ACCEL_PROFILE_FLAT,
+                AccelProfile::Adaptive => ACCEL_PROFILE_ADAPTIVE
+            };
+            statuses.push(("accel_profile",
+                           libinput_device_config_accel_set_profile(handle, profile)));
+        }
+        if let Some(speed) = config.accel_speed {
+            statuses.push(("accel_speed",
+                           libinput_device_config_accel_set_speed(handle,
+                                                                  speed.max(-1.0).min(1.0))));
+        }
+        if let Some(left_handed) = config.left_handed {
+            statuses.push(("left_handed",
+                           libinput_device_config_left_handed_set(handle, left_handed as c_int)));
+        }
+        if let Some(enable) = config.disable_while_typing {
+            statuses.push(("disable_while_typing",
+                           libinput_device_config_dwt_set_enabled(handle, enable as c_int)));
+        }
+        if let Some(method) = config.scroll_method {
+            let method = match method {
+                ScrollMethod::NoScroll => SCROLL_NO_SCROLL,
+                ScrollMethod::TwoFinger => SCROLL_2FG,
+                ScrollMethod::Edge => SCROLL_EDGE,
+                ScrollMethod::OnButtonDown => SCROLL_ON_BUTTON_DOWN
+            };
+            statuses.push(("scroll_method",
+                           libinput_device_config_scroll_set_method(handle, method)));
+        }
+        if let Some(enable) = config.middle_emulation {
+            statuses.push(("middle_emulation",
+                           libinput_device_config_middle_emulation_set_enabled(handle,
+                                                                               enable as
+                                                                               c_int)));
+        }
+        for (setting, status) in statuses {
+            if status != CONFIG_STATUS_SUCCESS {
+                debug!("{} does not support setting {}", name, setting);
+            }
+        }
+    }
+    config
+}
+
+/// Add a rule for configuring the matching current and future devices.
+pub fn configure_input(rule: InputRule) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        server.input_rules.push(rule);
+        {
+            let Server { ref input_rules,
+                         ref mut keyboards,
+                         ref mut pointers,
+                         ref mut touches,
+                         ref mut tablets,
+                         .. } = *server;
+            for keyboard in keyboards {
+                run_handles!([(keyboard: {keyboard})] => {
+                    configure_device(input_rules, keyboard.input_device(), InputType::Keyboard)
+                }).expect("Keyboard was destroyed");
+            }
+            for pointer in pointers {
+                run_handles!([(pointer: {pointer})] => {
+                    let device = pointer.input_device();
+                    configure_device(input_rules, device, input_type(device, InputType::Pointer))
+                }).expect("Pointer was destroyed");
+            }
+            for touch in touches {
+                run_handles!([(touch: {touch})] => {
+                    configure_device(input_rules, touch.input_device(), InputType::Touch)
+                }).expect("Touch was destroyed");
+            }
+            for tablet in tablets {
+                run_handles!([(tablet: {&mut tablet.handle})] => {
+                    configure_device(input_rules, tablet.input_device(), InputType::Tablet)
+                }).expect("Tablet was destroyed");
+            }
+        }
+        map_devices_to_outputs(server)
+    });
+}
+
+/// Map the touch screens and tablets to the outputs they are configured
+/// for.
+///
+/// This is done again when an output is added, because a device can be
+/// configured for an output that wasn't there yet.
+pub fn map_devices_to_outputs(server: &mut Server) {
+    let Server { ref input_rules,
+                 ref mut cursor,
+                 ref mut layout,
+                 ref mut touches,
+                 ref mut tablets,
+                 .. } = *server;
+    for touch in touches {
+        run_handles!([(touch: {touch})] => {
+            let device = touch.input_device();
+            let config = input_config(input_rules, &device.name(), InputType::Touch);
+            compositor::map_to_output(cursor,
+                                      layout,
+                                      device,
+                                      config.output.as_ref().map(String::as_str))
+        }).expect("Touch was destroyed")
+          .expect("Could not map touch to output");
+    }
+    for tablet in tablets {
+        run_handles!([(tablet: {&mut tablet.handle})] => {
+            let device = tablet.input_device();
+            let config = input_config(input_rules, &device.name(), InputType::Tablet);
+            compositor::map_to_output(cursor,
+                                      layout,
+                                      device,
+                                      config.output.as_ref().map(String::as_str))
+        }).expect("Tablet was destroyed")
+          .expect("Could not map tablet to output");
+    }
+}
+
+/// Determine if the devices are part of the same physical device, e.g a
+/// tablet and its pad.
+pub fn same_device_group(device: &InputDevice, other: &InputDevice) -> bool {
+    unsafe {
+        match (libinput_handle(device), libinput_handle(other)) {
+            (Some(device), Some(other)) => {
+                libinput_device_get_device_group(device) == libinput_device_get_device_group(other)
+            }
+            _ => false
+        }
+    }
+}
+
+/// An id for the device that is unique while it's plugged in.
+///
+/// Devices with more than one capability (e.g a keyboard with a touchpad)
+/// have a different id for each of them.
+pub fn device_id(device: &InputDevice) -> usize {
+    device.as_ptr() as usize
+}
+
+unsafe fn libinput_handle(device: &InputDevice) -> Option<*mut libinput_device> {
+    let device = device.as_ptr();
+    if wlr_input_device_is_libinput(device) {
+        Some(wlr_libinput_get_device_handle(device))
+    } else {
+        None
+    }
+}
@@ -1,7 +1,15 @@
+mod constraints;
 mod input_manager;
 mod keyboard;
+mod libinput;
 mod pointer;
+mod tablet;
+mod touch;
 
+pub use self::constraints::*;
 pub use self::input_manager::*;
 pub use self::keyboard::*;
+pub use self::libinput::*;
 pub use self::pointer::*;
+pub use self::tablet::*;
+pub use self::touch::*;
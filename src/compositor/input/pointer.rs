@@ -1,7 +1,10 @@
-use compositor::{self, Action, Server, Shell, View};
-use std::time::Duration;
+use awesome;
+use compositor::{self, Action, CursorTheme, FocusPolicy, Gesture, Server, Shell, TitlebarSide,
+                 View};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use wlroots::{self, Compositor, CursorHandle, HandleResult, KeyboardHandle, Origin,
-              PointerHandler, XdgV6ShellState::*, pointer_events::*, WLR_BUTTON_RELEASED};
+              OutputLayoutHandle, PointerHandler, SurfaceHandle, XdgV6ShellState::*,
+              pointer_events::*, WLR_BUTTON_RELEASED};
 
 #[derive(Debug, Default)]
 pub struct Pointer;
@@ -13,23 +16,73 @@ impl PointerHandler for Pointer {
                  event: &MotionEvent) {
         let server: &mut Server = compositor.into();
         let Server { ref mut cursor,
+                     ref mut layout,
                      ref mut seat,
                      ref mut views,
+                     ref mut keyboards,
+                     ref mut cursor_theme,
+                     ref mut relative_pointer_manager,
+                     ref mut idle,
+                     ref mut lock,
                      .. } = *server;
+        compositor::notify_activity(idle, seat);
+        let time = Duration::from_millis(event.time_msec() as _);
+        let (dx, dy) = event.delta();
+        let (udx, udy) = event.unaccel_delta();
+        // Clients with a locked pointer (e.g games) still get relative motion.
+        run_handles!([(relative_pointer_manager: {&mut *relative_pointer_manager}),
+                      (seat: {&mut seat.seat})] => {
+            relative_pointer_manager.send_relative_motion(seat, time, dx, dy, udx, udy);
+        }).expect("Could not send relative motion");
+        let position = run_handles!([(cursor: {&mut *cursor})] => {
+            cursor.coords()
+        }).expect("Cursor was destroyed");
+        let (dx, dy) = if lock.locked {
+            (dx, dy)
+        } else {
+            compositor::constrain_motion(seat, position, (dx, dy))
+                .expect("Could not constrain pointer")
+        };
         run_handles!([(cursor: {&mut *cursor})] => {
-            let (x, y) = event.delta();
-            cursor.move_to(event.device(), x, y);
+            cursor.move_to(event.device(), dx, dy);
         }).expect("Cursor was destroyed");
+        update_lua_position(cursor, layout).expect("Cursor was destroyed");
+        if lock.locked {
+            compositor::lock_pointer_motion(lock, seat, layout, cursor, time)
+                .expect("Could not send motion to lock surface");
+            return
+        }
+        if seat.drag.is_some() {
+            compositor::drag_motion(seat, views, cursor, time).expect("Could not send drag motion");
+            return
+        }
+        // The grab gets the motion instead of the views.
+        if awesome::mousegrabber_running() {
+            let (x, y) = run_handles!([(cursor: {&mut *cursor})] => {
+                cursor.coords()
+            }).expect("Cursor was destroyed");
+            awesome::mousegrabber_handle(x as i32, y as i32)
+                .unwrap_or_else(|err| warn!("Could not run mousegrabber: {}", err));
+            return
+        }
         match seat.action {
             Some(Action::Moving { start }) => {
-                if let Some(mut view) = view_at_pointer(views, cursor) {
+                if let Some((view, ..)) = view_at_pointer(views, cursor) {
                     let meta_held_down = seat.meta;
                     if meta_held_down {
-                        move_view(seat, cursor, &mut view, start).expect("Could not move view");
+                        move_view(seat, cursor, view, start).expect("Could not move view");
                     }
                 }
             }
-            _ => { /* TODO */ }
+            None => {
+                update_hovered(seat,
+                               &mut **keyboards,
+                               views,
+                               cursor,
+                               cursor_theme,
+                               time,
+                               false).expect("Could not update the view under the pointer")
+            }
         }
     }
 
@@ -39,16 +92,99 @@ impl PointerHandler for Pointer {
                  event: &ButtonEvent) {
         let server: &mut Server = compositor.into();
         let Server { ref mut cursor,
+                     ref mut layout,
                      ref mut views,
                      ref mut seat,
                      ref mut keyboards,
+                     ref mut cursor_theme,
+                     ref mut idle,
+                     ref mut lock,
                      .. } = *server;
-        if event.state() == WLR_BUTTON_RELEASED {
+        compositor::notify_activity(idle, seat);
+        {
+            let mut pointer = awesome::POINTER.lock().expect("Pointer was poisoned");
+            if event.state() == WLR_BUTTON_RELEASED {
+                pointer.buttons.retain(|button| *button != event.button());
+            } else {
+                pointer.buttons.push(event.button());
+            }
+        }
+        let (x, y) = run_handles!([(cursor: {&mut *cursor})] => {
+            cursor.coords()
+        }).expect("Cursor was destroyed");
+        let pressed = event.state() != WLR_BUTTON_RELEASED;
+        if lock.locked {
+            // Clicking a lock surface on another output gives it the keyboard.
+            let under_pointer = match compositor::lock_surface_at(lock, layout, (x, y)) {
+                Ok(under_pointer) => under_pointer,
+                Err(_) => {
+                    warn!("Could not find lock surface, it was destroyed");
+                    None
+                }
+            };
+            if let (true, Some((surface, ..))) = (pressed, under_pointer) {
+                match compositor::focus_lock_surface(seat, &mut **keyboards, surface.clone()) {
+                    Ok(()) => lock.focused = Some(surface),
+                    Err(_) => warn!("Could not focus lock surface, it was destroyed")
+                }
+            }
+            send_pointer_button(seat, event).expect("Could not send pointer button");
+            return
+        }
+        // The drag has the pointer until the button is let go.
+        if seat.drag.is_some() {
+            if !pressed {
+                let time = Duration::from_millis(event.time_msec() as _);
+                compositor::drop_drag(seat, &mut **keyboards, views, cursor, cursor_theme, time)
+                    .expect("Could not drop drag");
+            }
+            return
+        }
+        if awesome::mousegrabber_running() {
+            awesome::mousegrabber_handle(x as i32, y as i32)
+                .unwrap_or_else(|err| warn!("Could not run mousegrabber: {}", err));
+            if !pressed {
+                seat.action = None;
+            }
+            return
+        }
+        let mods = compositor::active_modifiers(keyboards);
+        let over_drawin = awesome::drawin_button(x, y, event.button(), pressed, mods);
+        let titlebar = if over_drawin {
+            None
+        } else {
+            compositor::titlebar_at(views, x, y).expect("Could not find titlebar")
+        };
+        if let Some((view_id, side, tx, ty)) = titlebar {
+            awesome::titlebar_button(view_id, side, tx, ty, event.button(), pressed, mods);
+        }
+        let over_root = !over_drawin
+                        && compositor::decorated_view_at(views, x, y)
+                           .expect("Could not find view under pointer")
+                           .is_none()
+                        && view_at_pointer(views, cursor).is_none();
+        if over_root {
+            awesome::root_button(event.button(), mods, pressed);
+        }
+        if !pressed {
             seat.action = None;
             send_pointer_button(seat, event).expect("Could not send pointer button");
             return
         }
-        if let Some(view) = view_at_pointer(views, cursor) {
+        // Drawins are drawn above the views, so they get the click instead.
+        if over_drawin {
+            return
+        }
+        // Clicking the titlebars or border of a view focuses it too.
+        let decorated = compositor::decorated_view_at(views, x, y)
+            .expect("Could not find view under pointer");
+        if let Some(view_id) = decorated {
+            if let Some(view) = views.iter_mut().find(|view| view.id == view_id) {
+                focus_under_pointer(seat, &mut **keyboards, view).expect("Could not focus view");
+            }
+            return
+        }
+        if let Some((view, ..)) = view_at_pointer(views, cursor) {
             focus_under_pointer(seat, &mut **keyboards, { &mut *view }).expect("Could not focus \
                                                                                 view");
             let meta_held_down = seat.meta;
@@ -60,36 +196,379 @@ impl PointerHandler for Pointer {
             focus_under_pointer(seat, &mut **keyboards, None).expect("Could not focus view");
         }
     }
+
+    fn on_swipe_begin(&mut self,
+                      compositor: &mut Compositor,
+                      _: &mut wlroots::Pointer,
+                      event: &SwipeBeginEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut seat,
+                     ref mut pointer_gestures,
+                     ref mut idle,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        seat.gesture = Some(Gesture::Swipe { fingers: event.fingers(),
+                                             dx: 0.0,
+                                             dy: 0.0 });
+        let time = Duration::from_millis(event.time_msec() as _);
+        run_handles!([(pointer_gestures: {&mut *pointer_gestures}),
+                      (seat: {&mut seat.seat})] => {
+            pointer_gestures.send_swipe_begin(seat, time, event.fingers());
+        }).expect("Could not send swipe begin");
+    }
+
+    fn on_swipe_update(&mut self,
+                       compositor: &mut Compositor,
+                       _: &mut wlroots::Pointer,
+                       event: &SwipeUpdateEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut seat,
+                     ref mut pointer_gestures,
+                     ref mut idle,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        let (dx, dy) = event.delta();
+        if let Some(Gesture::Swipe { dx: ref mut total_dx,
+                                     dy: ref mut total_dy,
+                                     .. }) = seat.gesture
+        {
+            *total_dx += dx;
+            *total_dy += dy;
+        }
+        let time = Duration::from_millis(event.time_msec() as _);
+        run_handles!([(pointer_gestures: {&mut *pointer_gestures}),
+                      (seat: {&mut seat.seat})] => {
+            pointer_gestures.send_swipe_update(seat, time, dx, dy);
+        }).expect("Could not send swipe update");
+    }
+
+    fn on_swipe_end(&mut self,
+                    compositor: &mut Compositor,
+                    _: &mut wlroots::Pointer,
+                    event: &SwipeEndEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut seat,
+                     ref mut pointer_gestures,
+                     ref mut idle,
+                     ref lock,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        let time = Duration::from_millis(event.time_msec() as _);
+        run_handles!([(pointer_gestures: {&mut *pointer_gestures}),
+                      (seat: {&mut seat.seat})] => {
+            pointer_gestures.send_swipe_end(seat, time, event.cancelled());
+        }).expect("Could not send swipe end");
+        if let Some(Gesture::Swipe { fingers, dx, dy }) = seat.gesture.take() {
+            if !event.cancelled() && !lock.locked {
+                awesome::gesture_swipe(fingers, dx, dy);
+            }
+        }
+    }
+
+    fn on_pinch_begin(&mut self,
+                      compositor: &mut Compositor,
+                      _: &mut wlroots::Pointer,
+                      event: &PinchBeginEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut seat,
+                     ref mut pointer_gestures,
+                     ref mut idle,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        seat.gesture = Some(Gesture::Pinch { fingers: event.fingers(),
+                                             scale: 1.0,
+                                             rotation: 0.0 });
+        let time = Duration::from_millis(event.time_msec() as _);
+        run_handles!([(pointer_gestures: {&mut *pointer_gestures}),
+                      (seat: {&mut seat.seat})] => {
+            pointer_gestures.send_pinch_begin(seat, time, event.fingers());
+        }).expect("Could not send pinch begin");
+    }
+
+    fn on_pinch_update(&mut self,
+                       compositor: &mut Compositor,
+                       _: &mut wlroots::Pointer,
+                       event: &PinchUpdateEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut seat,
+                     ref mut pointer_gestures,
+                     ref mut idle,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        let (dx, dy) = event.delta();
+        if let Some(Gesture::Pinch { ref mut scale,
+                                     ref mut rotation,
+                                     .. }) = seat.gesture
+        {
+            // The scale is absolute, but the rotation is a delta.
+            *scale = event.scale();
+            *rotation += event.rotation();
+        }
+        let time = Duration::from_millis(event.time_msec() as _);
+        run_handles!([(pointer_gestures: {&mut *pointer_gestures}),
+                      (seat: {&mut seat.seat})] => {
+            pointer_gestures.send_pinch_update(seat,
+                                               time,
+                                               dx,
+                                               dy,
+                                               event.scale(),
+                                               event.rotation());
+        }).expect("Could not send pinch update");
+    }
+
+    fn on_pinch_end(&mut self,
+                    compositor: &mut Compositor,
+                    _: &mut wlroots::Pointer,
+                    event: &PinchEndEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut seat,
+                     ref mut pointer_gestures,
+                     ref mut idle,
+                     ref lock,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        let time = Duration::from_millis(event.time_msec() as _);
+        run_handles!([(pointer_gestures: {&mut *pointer_gestures}),
+                      (seat: {&mut seat.seat})] => {
+            pointer_gestures.send_pinch_end(seat, time, event.cancelled());
+        }).expect("Could not send pinch end");
+        if let Some(Gesture::Pinch { fingers,
+                                     scale,
+                                     rotation }) = seat.gesture.take()
+        {
+            if !event.cancelled() && !lock.locked {
+                awesome::gesture_pinch(fingers, scale, rotation);
+            }
+        }
+    }
 }
 
+/// Gets the top most view under the pointer, along with the surface of that
+/// view that is under the pointer.
+///
+/// The surface can be a subsurface or a popup of the view, which is why the
+/// coordinates returned are local to that surface.
 fn view_at_pointer<'view>(views: &'view mut [View],
                           cursor: &mut CursorHandle)
-                          -> Option<&'view mut View> {
-    for view in views {
-        match view.shell.clone() {
-            Shell::XdgV6(ref mut shell) => {
-                let (mut sx, mut sy) = (0.0, 0.0);
-                let seen = run_handles!([(shell: {&mut *shell}),
-                                         (cursor: {&mut *cursor})] => {
-                    let (lx, ly) = cursor.coords();
-                    let Origin {x: shell_x, y: shell_y} = view.origin;
-                    let (view_sx, view_sy) = (lx - shell_x as f64, ly - shell_y as f64);
-                    shell.surface_at(view_sx, view_sy, &mut sx, &mut sy).is_some()
-                }).ok()?;
-                if seen {
-                    return Some(view)
-                }
-            }
+                          -> Option<(&'view mut View, SurfaceHandle, f64, f64)> {
+    let (lx, ly) = run_handles!([(cursor: {&mut *cursor})] => {
+        cursor.coords()
+    }).ok()?;
+    view_at(views, lx, ly)
+}
+
+/// Gets the top most view at the output layout coordinates, along with the
+/// surface of that view at those coordinates.
+pub fn view_at<'view>(views: &'view mut [View],
+                      lx: f64,
+                      ly: f64)
+                      -> Option<(&'view mut View, SurfaceHandle, f64, f64)> {
+    for view in views.iter_mut().rev() {
+        if let Some((surface, sx, sy)) = view.surface_at(lx, ly).ok()? {
+            return Some((view, surface, sx, sy))
+        }
+        // Titlebars and borders aren't surfaces, but they hide the views below
+        // them.
+        if view.contains(lx, ly).ok()? {
+            return None
         }
     }
     None
 }
 
+/// Send pointer enter, leave and motion events to the surface under the
+/// pointer.
+///
+/// If the pointer entered a different view it is focused according to the
+/// focus policy of the seat, unless `ignore_enter` is set.
+pub fn update_hovered(seat: &mut compositor::Seat,
+                      keyboards: &mut [KeyboardHandle],
+                      views: &mut [View],
+                      cursor: &mut CursorHandle,
+                      cursor_theme: &mut CursorTheme,
+                      time: Duration,
+                      ignore_enter: bool)
+                      -> HandleResult<()> {
+    let (lx, ly) = run_handles!([(cursor: {&mut *cursor})] => {
+        cursor.coords()
+    })?;
+    // Drawins are drawn above the views, so they shadow them.
+    let over_drawin = awesome::over_drawin(lx, ly);
+    let titlebar = if over_drawin {
+        None
+    } else {
+        compositor::titlebar_at(views, lx, ly)?
+    };
+    update_hovered_titlebar(seat, titlebar);
+    let under_pointer = if over_drawin {
+        None
+    } else {
+        view_at_pointer(views, cursor).map(|(view, surface, sx, sy)| {
+                                          (view.clone(), surface, sx, sy)
+                                      })
+    };
+    let hovered = match under_pointer {
+        Some((view, surface, sx, sy)) => {
+            run_handles!([(seat: {&mut seat.seat}),
+                          (surface: {surface})] => {
+                seat.pointer_notify_enter(surface, sx, sy);
+                seat.pointer_notify_motion(time, sx, sy);
+            })?;
+            Some(view)
+        }
+        None => {
+            run_handles!([(seat: {&mut seat.seat})] => {
+                seat.pointer_clear_focus();
+            })?;
+            // Clients set their own cursor image, so put ours back.
+            if seat.hovered.is_some() {
+                cursor_theme.reset_image(cursor)?;
+            }
+            None
+        }
+    };
+    awesome::POINTER.lock().expect("Pointer was poisoned").view =
+        hovered.as_ref().map(|view| view.id);
+    if seat.hovered == hovered {
+        return Ok(())
+    }
+    seat.hovered = hovered;
+    seat.hover_serial = seat.hover_serial.wrapping_add(1);
+    // Don't steal the focus from drawins or grabs.
+    if ignore_enter || over_drawin || awesome::mousegrabber_running() {
+        return Ok(())
+    }
+    focus_hovered(seat, keyboards)
+}
+
+/// Let Lua know the pointer entered, moved over or left a titlebar.
+fn update_hovered_titlebar(seat: &mut compositor::Seat,
+                           titlebar: Option<(usize, TitlebarSide, f64, f64)>) {
+    let hovered = titlebar.map(|(view_id, side, ..)| (view_id, side));
+    if seat.hovered_titlebar != hovered {
+        if let Some((view_id, side)) = seat.hovered_titlebar.take() {
+            awesome::titlebar_mouse(view_id, side, "mouse::leave", None);
+        }
+        if let Some((view_id, side)) = hovered {
+            awesome::titlebar_mouse(view_id, side, "mouse::enter", None);
+        }
+        seat.hovered_titlebar = hovered;
+    }
+    if let Some((view_id, side, x, y)) = titlebar {
+        awesome::titlebar_mouse(view_id, side, "mouse::move", Some((x, y)));
+    }
+}
+
+/// Warp the cursor to the output layout coordinates.
+///
+/// If the coordinates are outside of the layout, the cursor is warped to the
+/// closest point in the layout instead.
+pub fn warp_cursor((x, y): (f64, f64), ignore_enter: bool) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut cursor,
+                     ref mut layout,
+                     ref mut seat,
+                     ref mut views,
+                     ref mut keyboards,
+                     ref mut cursor_theme,
+                     ref mut lock,
+                     .. } = *server;
+        run_handles!([(cursor: {&mut *cursor})] => {
+            if !cursor.warp(None, x, y) {
+                cursor.warp_closest(None, x, y);
+            }
+        }).expect("Cursor was destroyed");
+        update_lua_position(cursor, layout).expect("Cursor was destroyed");
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)
+                                    .expect("Time went backwards");
+        if lock.locked {
+            compositor::lock_pointer_motion(lock, seat, layout, cursor, time)
+                .expect("Could not send motion to lock surface");
+        } else if seat.drag.is_some() {
+            compositor::drag_motion(seat, views, cursor, time).expect("Could not send drag motion")
+        } else if seat.action.is_none() {
+            update_hovered(seat,
+                           &mut **keyboards,
+                           views,
+                           cursor,
+                           cursor_theme,
+                           time,
+                           ignore_enter).expect("Could not update the view under the pointer")
+        }
+    });
+}
+
+/// Let Lua know where the cursor is, and which output it is on.
+pub fn update_lua_position(cursor: &mut CursorHandle,
+                           layout: &mut OutputLayoutHandle)
+                           -> HandleResult<()> {
+    let (position, output) = run_handles!([(cursor: {cursor}), (layout: {layout})] => {
+        let (x, y) = cursor.coords();
+        let output = layout.output_at(x, y)
+            .and_then(|mut output| output.run(|output| output.name()).ok());
+        ((x, y), output)
+    })?;
+    let mut pointer = awesome::POINTER.lock().expect("Pointer was poisoned");
+    pointer.position = position;
+    pointer.output = output;
+    Ok(())
+}
+
+/// Focus the hovered view, if the focus policy allows it.
+///
+/// If the seat has a focus delay the view is only focused if the pointer is
+/// still on it once the delay has passed.
+fn focus_hovered(seat: &mut compositor::Seat,
+                 keyboards: &mut [KeyboardHandle])
+                 -> HandleResult<()> {
+    match (seat.focus_policy, seat.hovered.is_some()) {
+        (FocusPolicy::ClickToFocus, _) | (FocusPolicy::Sloppy, false) => return Ok(()),
+        _ => {}
+    }
+    if seat.focus_delay == Duration::from_secs(0) {
+        let mut hovered = seat.hovered.clone();
+        if seat.focused == hovered {
+            return Ok(())
+        }
+        return focus_under_pointer(seat, keyboards, hovered.as_mut())
+    }
+    let serial = seat.hover_serial;
+    compositor::run_after(seat.focus_delay, move |compositor| {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut seat,
+                     ref mut keyboards,
+                     ref lock,
+                     .. } = *server;
+        if seat.hover_serial != serial || seat.action.is_some() || lock.locked {
+            return
+        }
+        let mut hovered = seat.hovered.clone();
+        if seat.focused != hovered {
+            focus_under_pointer(seat, &mut **keyboards, hovered.as_mut())
+                .expect("Could not focus view");
+        }
+    });
+    Ok(())
+}
+
 /// Focus the view under the pointer.
-fn focus_under_pointer<'view, V>(seat: &mut compositor::Seat,
-                                 keyboards: &mut [KeyboardHandle],
-                                 view: V)
-                                 -> HandleResult<()>
+///
+/// The pointer constraint of the newly focused view, if any, is activated.
+pub fn focus_under_pointer<'view, V>(seat: &mut compositor::Seat,
+                                     keyboards: &mut [KeyboardHandle],
+                                     view: V)
+                                     -> HandleResult<()>
+    where V: Into<Option<&'view mut View>>
+{
+    focus_view(seat, keyboards, view)?;
+    compositor::update_constraint(seat)
+}
+
+fn focus_view<'view, V>(seat: &mut compositor::Seat,
+                        keyboards: &mut [KeyboardHandle],
+                        view: V)
+                        -> HandleResult<()>
     where V: Into<Option<&'view mut View>>
 {
     // TODO Use those surface level coordinates to send events and shit
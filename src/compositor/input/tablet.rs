@@ -0,0 +1,394 @@
+//! Drawing tablets.
+//!
+//! Clients that support the tablet protocol get the events of the tools
+//! directly, for other clients the tools act like the pointer.
+
+use awesome;
+use compositor::{self, Server};
+use std::time::Duration;
+use wlroots::{self, Compositor, CursorHandle, HandleResult, InputDevice, OutputLayoutHandle,
+              SurfaceHandle, TabletPadHandle, TabletPadHandler, TabletPadV2Handle,
+              TabletToolHandle, TabletToolHandler, TabletToolV2Handle, TabletV2Handle,
+              pointer_events::BTN_LEFT, tablet_pad_events, tablet_tool_events::*,
+              WLR_BUTTON_PRESSED, WLR_BUTTON_RELEASED};
+
+/// A tablet and the tools that have been used on it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tablet {
+    pub handle: TabletToolHandle,
+    /// The tablet as it is advertised to clients.
+    pub tablet_v2: TabletV2Handle,
+    pub tools: Vec<Tool>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tool {
+    /// Identifies the tool, e.g the pen and the eraser of a stylus are
+    /// different tools.
+    pub id: u64,
+    /// The tool as it is advertised to clients.
+    pub tool_v2: TabletToolV2Handle,
+    /// The surface the tool is over, if that surface supports the tablet
+    /// protocol.
+    pub surface: Option<SurfaceHandle>
+}
+
+/// The buttons of a tablet pad.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pad {
+    pub handle: TabletPadHandle,
+    /// The pad as it is advertised to clients.
+    pub pad_v2: TabletPadV2Handle,
+    /// The tablet the pad is part of, if it's plugged in.
+    pub tablet: Option<TabletToolHandle>,
+    /// The surface that receives the pad events, which is the focused one.
+    pub surface: Option<SurfaceHandle>
+}
+
+#[derive(Debug, Default)]
+pub struct TabletHandler;
+
+#[derive(Debug, Default)]
+pub struct PadHandler;
+
+impl TabletToolHandler for TabletHandler {
+    fn on_axis(&mut self,
+               compositor: &mut Compositor,
+               tablet: &mut wlroots::TabletTool,
+               event: &AxisEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut cursor,
+                     ref mut layout,
+                     ref mut seat,
+                     ref mut views,
+                     ref mut keyboards,
+                     ref mut cursor_theme,
+                     ref mut tablets,
+                     ref mut tablet_manager,
+                     ref mut idle,
+                     ref mut lock,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        let (x, y) = event.location();
+        run_handles!([(cursor: {&mut *cursor})] => {
+            cursor.warp_absolute(event.device(), x, y);
+        }).expect("Cursor was destroyed");
+        compositor::update_lua_position(cursor, layout).expect("Cursor was destroyed");
+        let (lx, ly) = run_handles!([(cursor: {&mut *cursor})] => {
+            cursor.coords()
+        }).expect("Cursor was destroyed");
+        let tablet = match tablets.iter_mut().find(|t| t.handle == tablet.weak_reference()) {
+            Some(tablet) => tablet,
+            None => return
+        };
+        let tool = tablet_tool(tablet_manager, seat, tablet, event.tool());
+        if lock.locked {
+            // The lock surface only gets the tool as a pointer.
+            leave_surface(tool);
+            let time = Duration::from_millis(event.time_msec() as _);
+            compositor::lock_pointer_motion(lock, seat, layout, cursor, time)
+                .expect("Could not send motion to lock surface");
+            return
+        }
+        let under_tool = if awesome::over_drawin(lx, ly) {
+            None
+        } else {
+            compositor::view_at(views, lx, ly).map(|(_, surface, sx, sy)| (surface, sx, sy))
+        };
+        let accepted = match under_tool.clone() {
+            Some((surface, sx, sy)) => {
+                let time = Duration::from_millis(event.time_msec() as _);
+                let entered = tool.surface.as_ref() == Some(&surface);
+                run_handles!([(tablet_v2: {&mut tablet.tablet_v2}),
+                              (tool_v2: {&mut tool.tool_v2}),
+                              (surface: {surface})] => {
+                    if tablet_v2.accepts(surface) {
+                        if !entered {
+                            tool_v2.notify_proximity_in(tablet_v2, surface);
+                        }
+                        tool_v2.notify_motion(time, sx, sy);
+                        true
+                    } else {
+                        false
+                    }
+                }).unwrap_or(false)
+            }
+            None => false
+        };
+        if accepted {
+            tool.surface = under_tool.map(|(surface, ..)| surface);
+            return
+        }
+        leave_surface(tool);
+        // Act like the pointer for clients that don't know about tablets.
+        let time = Duration::from_millis(event.time_msec() as _);
+        compositor::update_hovered(seat,
+                                   &mut **keyboards,
+                                   views,
+                                   cursor,
+                                   cursor_theme,
+                                   time,
+                                   false).expect("Could not update the view under the pointer")
+    }
+
+    fn on_proximity(&mut self,
+                    compositor: &mut Compositor,
+                    tablet: &mut wlroots::TabletTool,
+                    event: &ProximityEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut seat,
+                     ref mut tablets,
+                     ref mut tablet_manager,
+                     ref mut idle,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        if event.state() == ProximityState::In {
+            return
+        }
+        if let Some(tablet) = tablets.iter_mut().find(|t| t.handle == tablet.weak_reference()) {
+            leave_surface(tablet_tool(tablet_manager, seat, tablet, event.tool()));
+        }
+    }
+
+    fn on_tip(&mut self,
+              compositor: &mut Compositor,
+              tablet: &mut wlroots::TabletTool,
+              event: &TipEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut cursor,
+                     ref mut seat,
+                     ref mut views,
+                     ref mut keyboards,
+                     ref mut tablets,
+                     ref mut tablet_manager,
+                     ref mut idle,
+                     ref lock,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        let down = event.state() == TipState::Down;
+        if let Some(tablet) = tablets.iter_mut().find(|t| t.handle == tablet.weak_reference()) {
+            let tool = tablet_tool(tablet_manager, seat, tablet, event.tool());
+            if tool.surface.is_some() {
+                run_handles!([(tool_v2: {&mut tool.tool_v2})] => {
+                    if down {
+                        tool_v2.notify_down();
+                    } else {
+                        tool_v2.notify_up();
+                    }
+                }).expect("Tablet tool was destroyed");
+                return
+            }
+        }
+        // Act like the left button for clients that don't know about tablets.
+        let (lx, ly) = run_handles!([(cursor: {&mut *cursor})] => {
+            cursor.coords()
+        }).expect("Cursor was destroyed");
+        {
+            let mut pointer = awesome::POINTER.lock().expect("Pointer was poisoned");
+            if down {
+                pointer.buttons.push(BTN_LEFT);
+            } else {
+                pointer.buttons.retain(|button| *button != BTN_LEFT);
+            }
+        }
+        let mods = compositor::active_modifiers(keyboards);
+        if !lock.locked && awesome::drawin_button(lx, ly, BTN_LEFT, down, mods) && down {
+            return
+        }
+        if down && !lock.locked {
+            if let Some((view, ..)) = compositor::view_at(views, lx, ly) {
+                compositor::focus_under_pointer(seat, &mut **keyboards, { &mut *view })
+                    .expect("Could not focus view");
+            }
+        }
+        let time = Duration::from_millis(event.time_msec() as _);
+        let state = if down {
+            WLR_BUTTON_PRESSED
+        } else {
+            WLR_BUTTON_RELEASED
+        };
+        run_handles!([(seat: {&mut seat.seat})] => {
+            seat.pointer_notify_button(time, BTN_LEFT, state as u32);
+        }).expect("Seat was destroyed");
+    }
+
+    fn on_button(&mut self,
+                 compositor: &mut Compositor,
+                 tablet: &mut wlroots::TabletTool,
+                 event: &ButtonEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut seat,
+                     ref mut tablets,
+                     ref mut tablet_manager,
+                     ref mut idle,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        if let Some(tablet) = tablets.iter_mut().find(|t| t.handle == tablet.weak_reference()) {
+            let tool = tablet_tool(tablet_manager, seat, tablet, event.tool());
+            if tool.surface.is_some() {
+                run_handles!([(tool_v2: {&mut tool.tool_v2})] => {
+                    tool_v2.notify_button(event.button(), event.state());
+                }).expect("Tablet tool was destroyed");
+            }
+        }
+    }
+}
+
+impl TabletPadHandler for PadHandler {
+    fn on_button(&mut self,
+                 compositor: &mut Compositor,
+                 pad: &mut wlroots::TabletPad,
+                 event: &tablet_pad_events::ButtonEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut seat,
+                     ref mut pads,
+                     ref mut tablets,
+                     ref mut idle,
+                     ref lock,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        if lock.locked {
+            return
+        }
+        let pad = match pads.iter_mut().find(|p| p.handle == pad.weak_reference()) {
+            Some(pad) => pad,
+            None => return
+        };
+        // Pad events go to the focused view, as long as it knows about tablets.
+        let focused = match seat.focused {
+            Some(ref mut view) => Some(view.shell.surface()),
+            None => None
+        };
+        if pad.surface.is_some() && pad.surface != focused {
+            leave_pad(pad);
+        }
+        let focused = match focused {
+            Some(focused) => focused,
+            None => return
+        };
+        let tablet = match pad.tablet {
+            Some(ref handle) => tablets.iter_mut().find(|tablet| tablet.handle == *handle),
+            None => None
+        };
+        let tablet = match tablet {
+            Some(tablet) => tablet,
+            None => return
+        };
+        let time = Duration::from_millis(event.time_msec() as _);
+        let entered = pad.surface.is_some();
+        let accepted = run_handles!([(pad_v2: {&mut pad.pad_v2}),
+                                     (tablet_v2: {&mut tablet.tablet_v2}),
+                                     (surface: {focused.clone()})] => {
+            if tablet_v2.accepts(surface) {
+                if !entered {
+                    pad_v2.notify_enter(tablet_v2, surface);
+                }
+                pad_v2.notify_button(time, event.button(), event.state());
+                true
+            } else {
+                false
+            }
+        }).unwrap_or(false);
+        pad.surface = if accepted { Some(focused) } else { None };
+    }
+}
+
+/// Get the tool that is used on the tablet, advertising it to clients if it
+/// hasn't been used before.
+fn tablet_tool<'tablet>(tablet_manager: &mut wlroots::TabletManagerHandle,
+                        seat: &mut compositor::Seat,
+                        tablet: &'tablet mut Tablet,
+                        tool: &wlroots::TabletToolTool)
+                        -> &'tablet mut Tool {
+    let id = tool.id();
+    if let Some(index) = tablet.tools.iter().position(|t| t.id == id) {
+        return &mut tablet.tools[index]
+    }
+    let tool_v2 = run_handles!([(tablet_manager: {tablet_manager}),
+                                (seat: {&mut seat.seat})] => {
+        tablet_manager.create_tool(seat, tool)
+    }).expect("Could not advertise tablet tool");
+    tablet.tools.push(Tool { id,
+                             tool_v2,
+                             surface: None });
+    tablet.tools.last_mut().unwrap()
+}
+
+/// Let the surface the tool was over know it has left.
+fn leave_surface(tool: &mut Tool) {
+    if tool.surface.take().is_some() {
+        run_handles!([(tool_v2: {&mut tool.tool_v2})] => {
+            tool_v2.notify_proximity_out();
+        }).expect("Tablet tool was destroyed");
+    }
+}
+
+/// Let the surface the pad was sending its events to know it has left.
+fn leave_pad(pad: &mut Pad) {
+    if let Some(surface) = pad.surface.take() {
+        // The surface may already be gone, in which case it doesn't care.
+        run_handles!([(pad_v2: {&mut pad.pad_v2}),
+                      (surface: {surface})] => {
+            pad_v2.notify_leave(surface);
+        }).unwrap_or(())
+    }
+}
+
+/// Find the tablet the pad is part of.
+pub fn pad_tablet(tablets: &mut [Tablet], pad: &InputDevice) -> Option<TabletToolHandle> {
+    tablets.iter_mut()
+           .find(|tablet| {
+                     run_handles!([(handle: {&mut tablet.handle})] => {
+                         compositor::same_device_group(handle.input_device(), pad)
+                     }).unwrap_or(false)
+                 })
+           .map(|tablet| tablet.handle.clone())
+}
+
+/// Give the pads that are part of the tablet, which was just plugged in,
+/// their tablet.
+pub fn attach_pads(pads: &mut [Pad], tablet: &wlroots::TabletTool) {
+    for pad in pads.iter_mut().filter(|pad| pad.tablet.is_none()) {
+        let same_group = run_handles!([(handle: {&mut pad.handle})] => {
+            compositor::same_device_group(handle.input_device(), tablet.input_device())
+        }).unwrap_or(false);
+        if same_group {
+            pad.tablet = Some(tablet.weak_reference());
+        }
+    }
+}
+
+/// Restrict the device to the output with the given name, or to the whole
+/// output layout if there is no name.
+pub fn map_to_output(cursor: &mut CursorHandle,
+                     layout: &mut OutputLayoutHandle,
+                     device: &InputDevice,
+                     output_name: Option<&str>)
+                     -> HandleResult<()> {
+    let output = match output_name {
+        None => None,
+        Some(name) => {
+            let output = run_handles!([(layout: {layout})] => {
+                layout.outputs().into_iter().map(|(output, _)| output).find(|output| {
+                    output.clone().run(|output| output.name() == name).unwrap_or(false)
+                })
+            })?;
+            if output.is_none() {
+                warn!("Can't map {} to {}, there is no such output", device.name(), name);
+            }
+            output
+        }
+    };
+    match output {
+        Some(output) => {
+            run_handles!([(cursor: {cursor}), (output: {output})] => {
+                cursor.map_input_to_output(device, Some(output));
+            })
+        }
+        None => {
+            run_handles!([(cursor: {cursor})] => {
+                cursor.map_input_to_output(device, None);
+            })
+        }
+    }
+}
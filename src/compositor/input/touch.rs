@@ -0,0 +1,192 @@
+//! Touchscreens.
+//!
+//! Touch points go to the surface they went down on until they are lifted,
+//! and touching a view focuses it. Drawins don't understand touch, so the
+//! first touch point on a drawin acts like the pointer instead.
+
+use awesome;
+use compositor::{self, Server};
+use std::time::Duration;
+use wlroots::{self, Compositor, CursorHandle, HandleResult, InputDevice, KeyboardHandle,
+              OutputLayoutHandle, TouchHandler, pointer_events::BTN_LEFT, touch_events::*};
+
+#[derive(Debug, Default)]
+pub struct Touch;
+
+/// A touch point that went down on a surface.
+///
+/// The surface keeps the touch point until it is lifted, even if it moves
+/// off the surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TouchPoint {
+    pub id: i32,
+    /// Where the surface was in the output layout when the touch point went
+    /// down.
+    pub origin: (f64, f64)
+}
+
+impl TouchHandler for Touch {
+    fn on_down(&mut self, compositor: &mut Compositor, _: &mut wlroots::Touch, event: &DownEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut cursor,
+                     ref mut layout,
+                     ref mut seat,
+                     ref mut views,
+                     ref mut keyboards,
+                     ref mut idle,
+                     ref mut lock,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        let (lx, ly) = layout_coords(cursor, event.device(), event.location())
+            .expect("Cursor was destroyed");
+        let time = Duration::from_millis(event.time_msec() as _);
+        if lock.locked {
+            let under_touch = compositor::lock_surface_at(lock, layout, (lx, ly))
+                .expect("Could not find lock surface");
+            if let Some((surface, sx, sy)) = under_touch {
+                run_handles!([(seat: {&mut seat.seat}),
+                              (surface: {surface})] => {
+                    seat.touch_notify_down(surface, time, event.touch_id(), sx, sy);
+                }).expect("Could not send touch down");
+                seat.touch_points.push(TouchPoint { id: event.touch_id(),
+                                                    origin: (lx - sx, ly - sy) });
+            }
+            return
+        }
+        // Drawins don't understand touch, so the first touch point on one
+        // acts like the pointer instead.
+        if seat.touch_emulation.is_none() && awesome::over_drawin(lx, ly) {
+            seat.touch_emulation = Some(event.touch_id());
+            emulate_button(cursor, layout, keyboards, (lx, ly), true)
+                .expect("Could not emulate pointer");
+            return
+        }
+        if let Some((view, surface, sx, sy)) = compositor::view_at(views, lx, ly) {
+            run_handles!([(seat: {&mut seat.seat}),
+                          (surface: {surface})] => {
+                seat.touch_notify_down(surface, time, event.touch_id(), sx, sy);
+            }).expect("Could not send touch down");
+            seat.touch_points.push(TouchPoint { id: event.touch_id(),
+                                                origin: (lx - sx, ly - sy) });
+            // Touching a view is like clicking it.
+            if seat.focused.as_ref() != Some(&*view) {
+                compositor::focus_under_pointer(seat, &mut **keyboards, { &mut *view })
+                    .expect("Could not focus view");
+            }
+        }
+    }
+
+    fn on_motion(&mut self,
+                 compositor: &mut Compositor,
+                 _: &mut wlroots::Touch,
+                 event: &MotionEvent) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut cursor,
+                     ref mut layout,
+                     ref mut seat,
+                     ref mut idle,
+                     .. } = *server;
+        compositor::notify_activity(idle, seat);
+        let (lx, ly) = layout_coords(cursor, event.device(), event.location())
+            .expect("Cursor was destroyed");
+        if seat.touch_emulation == Some(event.touch_id()) {
+            warp(cursor, layout, (lx, ly)).expect("Could not emulate pointer");
+            return
+        }
+        let touch_id = event.touch_id();
+        // The motion goes to the surface the touch point went down on, even
+        // when it's no longer over it.
+        let (x, y) = match seat.touch_points.iter().find(|point| point.id == touch_id) {
+            Some(point) => point.origin,
+            None => return
+        };
+        let time = Duration::from_millis(event.time_msec() as _);
+        run_handles!([(seat: {&mut seat.seat})] => {
+            seat.touch_notify_motion(time, touch_id, lx - x, ly - y);
+        }).expect("Seat was destroyed");
+    }
+
+    fn on_up(&mut self, compositor: &mut Compositor, _: &mut wlroots::Touch, event: &UpEvent) {
+        let server: &mut Server = compositor.into();
+        touch_up(server, event.touch_id(), event.time_msec())
+    }
+
+    fn on_cancel(&mut self,
+                 compositor: &mut Compositor,
+                 _: &mut wlroots::Touch,
+                 event: &CancelEvent) {
+        let server: &mut Server = compositor.into();
+        touch_up(server, event.touch_id(), event.time_msec())
+    }
+}
+
+fn touch_up(server: &mut Server, touch_id: i32, time_msec: u32) {
+    let Server { ref mut cursor,
+                 ref mut layout,
+                 ref mut seat,
+                 ref mut keyboards,
+                 ref mut idle,
+                 .. } = *server;
+    compositor::notify_activity(idle, seat);
+    if seat.touch_emulation == Some(touch_id) {
+        seat.touch_emulation = None;
+        let position = run_handles!([(cursor: {&mut *cursor})] => {
+            cursor.coords()
+        }).expect("Cursor was destroyed");
+        emulate_button(cursor, layout, keyboards, position, false)
+            .expect("Could not emulate pointer");
+        return
+    }
+    seat.touch_points.retain(|point| point.id != touch_id);
+    let time = Duration::from_millis(time_msec as _);
+    run_handles!([(seat: {&mut seat.seat})] => {
+        seat.touch_notify_up(time, touch_id);
+    }).expect("Seat was destroyed");
+}
+
+/// Map the location of the touch point, which is between 0 and 1 on the
+/// output the device is mapped to, to the output layout.
+fn layout_coords(cursor: &mut CursorHandle,
+                 device: &InputDevice,
+                 (x, y): (f64, f64))
+                 -> HandleResult<(f64, f64)> {
+    run_handles!([(cursor: {cursor})] => {
+        cursor.absolute_to_layout_coords(device, x, y)
+    })
+}
+
+/// Move the pointer to the touch point.
+fn warp(cursor: &mut CursorHandle,
+        layout: &mut OutputLayoutHandle,
+        (lx, ly): (f64, f64))
+        -> HandleResult<()> {
+    run_handles!([(cursor: {&mut *cursor})] => {
+        cursor.warp(None, lx, ly);
+    })?;
+    compositor::update_lua_position(cursor, layout)
+}
+
+/// Press or release the left button at the touch point, as if the pointer
+/// did it.
+fn emulate_button(cursor: &mut CursorHandle,
+                  layout: &mut OutputLayoutHandle,
+                  keyboards: &mut [KeyboardHandle],
+                  (lx, ly): (f64, f64),
+                  pressed: bool)
+                  -> HandleResult<()> {
+    warp(cursor, layout, (lx, ly))?;
+    {
+        let mut pointer = awesome::POINTER.lock().expect("Pointer was poisoned");
+        if pressed {
+            pointer.buttons.push(BTN_LEFT);
+        } else {
+            pointer.buttons.retain(|button| *button != BTN_LEFT);
+        }
+    }
+    awesome::drawin_button(lx,
+                           ly,
+                           BTN_LEFT,
+                           pressed,
+                           compositor::active_modifiers(keyboards));
+    Ok(())
+}
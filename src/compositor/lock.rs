@@ -0,0 +1,266 @@
+//! The session lock, which screen lockers use to lock the session.
+//!
+//! While the session is locked only the surfaces of the lock client are
+//! drawn and they get all of the input. If the lock client goes away
+//! without unlocking, the session stays locked with blank outputs until
+//! another lock client unlocks it.
+//!
+//! The lock client is only told that the session is locked once every output
+//! has shown a locked frame, so that it knows nothing else is visible.
+
+use compositor::{self, Seat, Server};
+use std::time::Duration;
+use wlroots::{Compositor, CursorHandle, HandleResult, KeyboardHandle, OutputHandle,
+              OutputLayoutHandle, SessionLock, SessionLockHandle, SessionLockHandler,
+              SessionLockManagerHandler, SessionLockSurface, SessionLockSurfaceHandle,
+              SurfaceHandle};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LockState {
+    /// If the session is locked, even when there is no lock client.
+    pub locked: bool,
+    /// The lock of the lock client.
+    pub lock: Option<SessionLockHandle>,
+    /// The surfaces that are drawn instead of everything else, one per output.
+    pub surfaces: Vec<LockSurface>,
+    /// The surface of the lock surface that has the keyboard.
+    pub focused: Option<SurfaceHandle>,
+    /// The outputs that have shown a locked frame since the lock client
+    /// locked the session.
+    pub presented: Vec<OutputHandle>,
+    /// If the lock client was told that the session is locked.
+    pub locked_sent: bool
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockSurface {
+    pub handle: SessionLockSurfaceHandle,
+    pub output: OutputHandle
+}
+
+#[derive(Debug, Default)]
+pub struct LockManager;
+
+#[derive(Debug, Default)]
+pub struct Lock;
+
+impl LockManager {
+    pub fn new() -> Self {
+        LockManager::default()
+    }
+}
+
+impl SessionLockManagerHandler for LockManager {
+    fn new_lock(&mut self,
+                compositor: &mut Compositor,
+                lock: &mut SessionLock)
+                -> Option<Box<SessionLockHandler>> {
+        let server: &mut Server = compositor.into();
+        let Server { lock: ref mut lock_state,
+                     ref mut seat,
+                     .. } = *server;
+        // Only one client can hold the lock at a time.
+        if lock_state.lock.is_some() {
+            return None
+        }
+        lock_state.locked = true;
+        lock_state.lock = Some(lock.weak_reference());
+        lock_state.presented.clear();
+        lock_state.locked_sent = false;
+        seat.action = None;
+        compositor::cancel_drag(seat).expect("Could not cancel drag");
+        run_handles!([(seat: {&mut seat.seat})] => {
+            seat.keyboard_clear_focus();
+            seat.pointer_clear_focus();
+        }).expect("Seat was destroyed");
+        Some(Box::new(Lock))
+    }
+}
+
+impl SessionLockHandler for Lock {
+    fn new_surface(&mut self,
+                   compositor: &mut Compositor,
+                   _: &mut SessionLock,
+                   surface: &mut SessionLockSurface) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut lock,
+                     ref mut seat,
+                     ref mut keyboards,
+                     .. } = *server;
+        let output = surface.output();
+        let (width, height) = match run_handles!([(output: {output.clone()})] => {
+            output.effective_resolution()
+        }) {
+            Ok(resolution) => resolution,
+            Err(_) => {
+                warn!("Ignoring lock surface, its output was destroyed");
+                return
+            }
+        };
+        surface.configure(width as u32, height as u32);
+        lock.surfaces.push(LockSurface { handle: surface.weak_reference(),
+                                         output });
+        focus_any_lock_surface(lock, seat, keyboards);
+    }
+
+    fn surface_destroyed(&mut self,
+                         compositor: &mut Compositor,
+                         _: &mut SessionLock,
+                         surface: &mut SessionLockSurface) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut lock,
+                     ref mut seat,
+                     ref mut keyboards,
+                     .. } = *server;
+        let weak_reference = surface.weak_reference();
+        lock.surfaces.retain(|surface| surface.handle != weak_reference);
+        // Otherwise the password can't be typed anymore.
+        if lock.focused == Some(surface.surface()) {
+            lock.focused = None;
+            focus_any_lock_surface(lock, seat, keyboards);
+        }
+    }
+
+    fn unlock(&mut self, compositor: &mut Compositor, _: &mut SessionLock) {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut lock,
+                     ref mut seat,
+                     ref mut keyboards,
+                     .. } = *server;
+        *lock = LockState::default();
+        // Give the keyboard back to the view that had it before locking.
+        let mut focused = seat.focused.take();
+        compositor::focus_under_pointer(seat, &mut **keyboards, focused.as_mut())
+            .expect("Could not focus view");
+    }
+
+    fn destroyed(&mut self, compositor: &mut Compositor, lock: &mut SessionLock) {
+        let server: &mut Server = compositor.into();
+        // If the lock client crashed the session stays locked.
+        if server.lock.lock == Some(lock.weak_reference()) {
+            server.lock.lock = None;
+            server.lock.surfaces.clear();
+            server.lock.focused = None;
+        }
+    }
+}
+
+/// Note that the output has shown a locked frame, and tell the lock client
+/// that the session is locked once every output has.
+pub fn locked_frame_presented(lock: &mut LockState,
+                              layout: &mut OutputLayoutHandle,
+                              output: OutputHandle)
+                              -> HandleResult<()> {
+    if !lock.locked || lock.locked_sent {
+        return Ok(())
+    }
+    if !lock.presented.contains(&output) {
+        lock.presented.push(output);
+    }
+    let outputs = run_handles!([(layout: {layout})] => {
+        layout.outputs()
+    })?;
+    if !outputs.iter().all(|&(ref output, _)| lock.presented.contains(output)) {
+        return Ok(())
+    }
+    lock.locked_sent = true;
+    if let Some(ref mut handle) = lock.lock {
+        run_handles!([(handle: {handle})] => {
+            handle.send_locked();
+        })?;
+    }
+    Ok(())
+}
+
+/// Get the lock surface at the output layout coordinates.
+///
+/// The coordinates returned are local to the surface found.
+pub fn lock_surface_at(lock: &mut LockState,
+                       layout: &mut OutputLayoutHandle,
+                       (lx, ly): (f64, f64))
+                       -> HandleResult<Option<(SurfaceHandle, f64, f64)>> {
+    let under = run_handles!([(layout: {layout})] => {
+        let output = layout.output_at(lx, ly);
+        layout.outputs().into_iter().find(|&(ref other, _)| Some(other) == output.as_ref())
+    })?;
+    let (output, origin) = match under {
+        Some(under) => under,
+        None => return Ok(None)
+    };
+    match lock.surfaces.iter_mut().find(|surface| surface.output == output) {
+        Some(surface) => {
+            let surface = run_handles!([(surface: {&mut surface.handle})] => {
+                surface.surface()
+            })?;
+            Ok(Some((surface, lx - origin.x as f64, ly - origin.y as f64)))
+        }
+        None => Ok(None)
+    }
+}
+
+/// Send the pointer to the lock surface under it.
+pub fn lock_pointer_motion(lock: &mut LockState,
+                           seat: &mut Seat,
+                           layout: &mut OutputLayoutHandle,
+                           cursor: &mut CursorHandle,
+                           time: Duration)
+                           -> HandleResult<()> {
+    let position = run_handles!([(cursor: {cursor})] => {
+        cursor.coords()
+    })?;
+    match lock_surface_at(lock, layout, position)? {
+        Some((surface, sx, sy)) => {
+            run_handles!([(seat: {&mut seat.seat}), (surface: {surface})] => {
+                seat.pointer_notify_enter(surface, sx, sy);
+                seat.pointer_notify_motion(time, sx, sy);
+            })
+        }
+        None => {
+            run_handles!([(seat: {&mut seat.seat})] => {
+                seat.pointer_clear_focus();
+            })
+        }
+    }
+}
+
+/// Give the keyboard to a lock surface if none has it.
+fn focus_any_lock_surface(lock: &mut LockState,
+                          seat: &mut Seat,
+                          keyboards: &mut [KeyboardHandle]) {
+    if lock.focused.is_some() {
+        return
+    }
+    for lock_surface in lock.surfaces.iter_mut() {
+        let surface = run_handles!([(handle: {&mut lock_surface.handle})] => {
+            handle.surface()
+        });
+        let surface = match surface {
+            Ok(surface) => surface,
+            Err(_) => continue
+        };
+        match focus_lock_surface(seat, keyboards, surface.clone()) {
+            Ok(()) => {
+                lock.focused = Some(surface);
+                return
+            }
+            Err(_) => warn!("Could not focus lock surface, it was destroyed")
+        }
+    }
+}
+
+/// Give the keyboard to the lock surface.
+pub fn focus_lock_surface(seat: &mut Seat,
+                          keyboards: &mut [KeyboardHandle],
+                          surface: SurfaceHandle)
+                          -> HandleResult<()> {
+    for keyboard in keyboards {
+        run_handles!([(seat: {&mut seat.seat}),
+                      (surface: {surface.clone()}),
+                      (keyboard: {keyboard})] => {
+            seat.keyboard_notify_enter(surface,
+                                       &mut keyboard.keycodes(),
+                                       &mut keyboard.get_modifier_masks())
+        })?;
+    }
+    Ok(())
+}
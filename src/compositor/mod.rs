@@ -1,44 +1,107 @@
 mod output;
 mod input;
 mod seat;
+mod selection;
 mod cursor;
+mod decoration;
+mod drag;
+mod idle;
+mod lock;
 mod shells;
 mod view;
 mod xwayland;
 
 pub use self::cursor::*;
+pub use self::decoration::*;
+pub use self::drag::*;
+pub use self::idle::*;
+pub use self::lock::*;
 pub use self::input::*;
 pub use self::output::*;
 pub use self::seat::*;
+pub use self::selection::*;
 pub use self::shells::*;
 pub use self::view::*;
 pub use self::xwayland::*;
 
-use wlroots::{self, Compositor, CompositorBuilder, Cursor, CursorHandle, KeyboardHandle,
-              OutputLayout, OutputLayoutHandle, PointerHandle, XCursorTheme};
+use glib;
+use std::time::Duration;
+use wlroots::{self, Compositor, CompositorBuilder, Cursor, CursorHandle, GammaControl,
+              GammaControlManagerHandle, Idle, IdleInhibit, IdleInhibitHandle, KeyboardHandle,
+              OutputLayout, OutputLayoutHandle, PointerConstraints, PointerConstraintsHandle,
+              PointerGestures, PointerGesturesHandle, PointerHandle, PrimarySelectionManager,
+              PrimarySelectionManagerHandle, RelativePointerManager, RelativePointerManagerHandle,
+              Screencopy, ScreencopyFrameHandle, ScreencopyHandle, SessionLockManager,
+              SessionLockManagerHandle, TabletManager, TabletManagerHandle, TouchHandle,
+              XdgDecorationManager, XdgDecorationManagerHandle};
 
 #[derive(Debug)]
 struct Server {
-    xcursor_theme: XCursorTheme,
+    cursor_theme: CursorTheme,
     layout: OutputLayoutHandle,
     seat: Seat,
     cursor: CursorHandle,
     keyboards: Vec<KeyboardHandle>,
     pointers: Vec<PointerHandle>,
-    views: Vec<View>
+    touches: Vec<TouchHandle>,
+    tablets: Vec<Tablet>,
+    pads: Vec<Pad>,
+    tablet_manager: TabletManagerHandle,
+    pointer_gestures: PointerGesturesHandle,
+    relative_pointer_manager: RelativePointerManagerHandle,
+    pointer_constraints: PointerConstraintsHandle,
+    idle: IdleState,
+    idle_inhibit: IdleInhibitHandle,
+    lock: LockState,
+    session_lock_manager: SessionLockManagerHandle,
+    screencopy: ScreencopyHandle,
+    /// Frames that screencopy clients are waiting for.
+    screencopy_frames: Vec<ScreencopyFrameHandle>,
+    /// Content that Lua is waiting for.
+    content_requests: Vec<ContentRequest>,
+    /// What Lua drew in its drawables.
+    drawables: Vec<DrawableTexture>,
+    gamma_control: GammaControlManagerHandle,
+    gamma: GammaState,
+    primary_selection: PrimarySelectionManagerHandle,
+    decoration_manager: XdgDecorationManagerHandle,
+    views: Vec<View>,
+    xkb_config: XkbConfig,
+    repeat_info: RepeatInfo,
+    input_rules: Vec<InputRule>
 }
 
 impl Default for Server {
     fn default() -> Server {
-        let xcursor_theme =
-            XCursorTheme::load_theme(None, 16).expect("Could not load default theme");
-        Server { xcursor_theme,
+        Server { cursor_theme: CursorTheme::default(),
                  layout: OutputLayoutHandle::default(),
                  seat: Seat::default(),
                  cursor: CursorHandle::default(),
                  keyboards: Vec::default(),
                  pointers: Vec::default(),
-                 views: Vec::default() }
+                 touches: Vec::default(),
+                 tablets: Vec::default(),
+                 pads: Vec::default(),
+                 tablet_manager: TabletManagerHandle::default(),
+                 pointer_gestures: PointerGesturesHandle::default(),
+                 relative_pointer_manager: RelativePointerManagerHandle::default(),
+                 pointer_constraints: PointerConstraintsHandle::default(),
+                 idle: IdleState::default(),
+                 idle_inhibit: IdleInhibitHandle::default(),
+                 lock: LockState::default(),
+                 session_lock_manager: SessionLockManagerHandle::default(),
+                 screencopy: ScreencopyHandle::default(),
+                 screencopy_frames: Vec::default(),
+                 content_requests: Vec::default(),
+                 drawables: Vec::default(),
+                 gamma_control: GammaControlManagerHandle::default(),
+                 gamma: GammaState::default(),
+                 primary_selection: PrimarySelectionManagerHandle::default(),
+                 decoration_manager: XdgDecorationManagerHandle::default(),
+                 views: Vec::default(),
+                 xkb_config: XkbConfig::default(),
+                 repeat_info: RepeatInfo::default(),
+                 input_rules: Vec::default() }
     }
 }
 
@@ -67,9 +130,90 @@ pub fn init() -> Compositor {
     let seat = wlroots::Seat::create(&mut compositor,
                                      "seat0".into(),
                                      Box::new(SeatManager::new()));
+    let tablet_manager = TabletManager::create(&mut compositor);
+    let pointer_gestures = PointerGestures::create(&mut compositor);
+    let relative_pointer_manager = RelativePointerManager::create(&mut compositor);
+    let pointer_constraints =
+        PointerConstraints::create(&mut compositor, Box::new(PointerConstraintsManager::new()));
+    let idle = Idle::create(&mut compositor);
+    let idle_inhibit = IdleInhibit::create(&mut compositor, Box::new(IdleInhibitManager::new()));
+    let session_lock_manager =
+        SessionLockManager::create(&mut compositor, Box::new(LockManager::new()));
+    let screencopy = Screencopy::create(&mut compositor, Box::new(ScreencopyManager::new()));
+    let gamma_control =
+        GammaControl::create(&mut compositor, Box::new(GammaControlManager::new()));
+    let primary_selection = PrimarySelectionManager::create(&mut compositor);
+    let decoration_manager =
+        XdgDecorationManager::create(&mut compositor, Box::new(DecorationManager::new()));
+    // XWayland clients share the selections through the seat.
+    let mut xwayland_seat = seat.clone();
+    if let Some(ref mut xwayland) = compositor.xwayland {
+        run_handles!([(seat: {&mut xwayland_seat})] => {
+            xwayland.set_seat(seat);
+        }).expect("Seat was destroyed");
+    }
     {
         let server: &mut Server = (&mut compositor).into();
         server.seat = Seat::new(seat);
+        server.tablet_manager = tablet_manager;
+        server.pointer_gestures = pointer_gestures;
+        server.relative_pointer_manager = relative_pointer_manager;
+        server.pointer_constraints = pointer_constraints;
+        server.idle.idle = idle;
+        server.idle_inhibit = idle_inhibit;
+        server.session_lock_manager = session_lock_manager;
+        server.screencopy = screencopy;
+        server.gamma_control = gamma_control;
+        server.primary_selection = primary_selection;
+        server.decoration_manager = decoration_manager;
     }
+    glib::timeout_add_seconds(1, || {
+                                  run_now(check_idle);
+                                  glib::Continue(true)
+                              });
     compositor
 }
+
+/// Run the function with the compositor once control returns to the event
+/// loop.
+///
+/// Code that is not called by the compositor (e.g Lua) must use this to
+/// modify the compositor, as the compositor is most likely already borrowed
+/// by the callback that ended up calling into Lua.
+pub fn run_later<F>(func: F)
+    where F: FnOnce(&mut Compositor) + Send + 'static
+{
+    let mut func = Some(func);
+    glib::idle_add(move || {
+                       if let Some(func) = func.take() {
+                           run_now(func)
+                       }
+                       glib::Continue(false)
+                   });
+}
+
+/// Run the function with the compositor once the delay has passed.
+pub fn run_after<F>(delay: Duration, func: F)
+    where F: FnOnce(&mut Compositor) + Send + 'static
+{
+    let mut func = Some(func);
+    let millis = delay.as_secs() as u32 * 1000 + delay.subsec_nanos() / 1_000_000;
+    glib::timeout_add(millis, move || {
+                          if let Some(func) = func.take() {
+                              run_now(func)
+                          }
+                          glib::Continue(false)
+                      });
+}
+
+fn run_now<F>(func: F)
+    where F: FnOnce(&mut Compositor)
+{
+    match wlroots::compositor_handle() {
+        Some(mut compositor) => {
+            compositor.run(func)
+                      .unwrap_or_else(|err| warn!("Could not run with the compositor: {:?}", err))
+        }
+        None => warn!("Compositor is not running")
+    }
+}
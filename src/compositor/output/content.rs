@@ -0,0 +1,315 @@
+//! Reading back what is on the outputs, e.g for screenshots from Lua.
+//!
+//! Lua can't wait for the outputs to be rendered, so it gets what was
+//! captured last and a new capture is asked for. Captures are filled in as
+//! the outputs they need are rendered, after which Lua is told with
+//! `content::changed`.
+
+use awesome;
+use cairo::{Format, ImageSurface};
+use compositor::{self, Server, View};
+use ipc::Content;
+use wlroots::{self, project_box, Area, HandleResult, Origin, OutputHandle, OutputLayoutHandle,
+              Renderer, Size, WL_SHM_FORMAT_ARGB8888};
+
+/// What to get the content of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentSource {
+    /// Every output, laid out like the output layout.
+    Root,
+    /// The output with the name.
+    Output(String),
+    /// The surface of the view with the id, without what is above it.
+    View(usize)
+}
+
+/// A capture that is filled in as the outputs it needs are rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentRequest {
+    pub source: ContentSource,
+    /// The area that is captured, in the output layout.
+    pub area: Area,
+    pub content: Content,
+    /// The outputs that still have to be rendered.
+    pub outputs: Vec<OutputHandle>
+}
+
+/// Get the content of the source as it was captured last, as a new cairo
+/// surface.
+///
+/// This doesn't render anything, as outputs can only be read back while
+/// they are rendered. The first time a source is read `None` is returned,
+/// and after that the last capture, which can be older than what is on the
+/// outputs. A new capture is asked for unless the content is from a capture
+/// that wasn't read before, and `content::changed` is emitted once it's
+/// there.
+pub fn content(source: ContentSource) -> Option<ImageSurface> {
+    let (latest, fresh) = {
+        let mut contents = awesome::CONTENTS.lock().expect("Contents was poisoned");
+        match contents.iter_mut().find(|&&mut (ref other, _)| *other == source) {
+            Some(&mut (_, ref mut content)) => {
+                let fresh = content.fresh;
+                content.fresh = false;
+                (Some(content.clone()), fresh)
+            }
+            None => (None, false)
+        }
+    };
+    if !fresh {
+        request_content(source);
+    }
+    latest.and_then(|content| content_to_surface(&content))
+}
+
+/// Copy the content into a new cairo surface.
+fn content_to_surface(content: &Content) -> Option<ImageSurface> {
+    let mut surface = ImageSurface::create(Format::ARgb32, content.width, content.height).ok()?;
+    {
+        let stride = surface.get_stride() as usize;
+        let row = content.width as usize * 4;
+        let mut data = surface.get_data().ok()?;
+        for y in 0..content.height as usize {
+            let line = &content.data[y * row..(y + 1) * row];
+            data[y * stride..y * stride + row].copy_from_slice(line);
+        }
+    }
+    Some(surface)
+}
+
+/// Capture the source the next time the outputs it needs are rendered.
+fn request_content(source: ContentSource) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut layout,
+                     ref mut views,
+                     ref mut content_requests,
+                     .. } = *server;
+        if content_requests.iter().any(|request| request.source == source) {
+            return
+        }
+        match content_request(layout, views, source) {
+            Ok(Some(request)) => content_requests.push(request),
+            Ok(None) => {}
+            Err(err) => warn!("Could not capture content: {:?}", err)
+        }
+    });
+}
+
+fn content_request(layout: &mut OutputLayoutHandle,
+                   views: &mut [View],
+                   source: ContentSource)
+                   -> HandleResult<Option<ContentRequest>> {
+    let layout_outputs = run_handles!([(layout: {layout})] => {
+        layout.outputs()
+    })?;
+    let mut outputs = Vec::with_capacity(layout_outputs.len());
+    for (mut output, origin) in layout_outputs {
+        let (name, (width, height)) = run_handles!([(output: {&mut output})] => {
+            (output.name(), output.effective_resolution())
+        })?;
+        outputs.push((output, name, Area::new(origin, Size::new(width, height))));
+    }
+    let area = match source {
+        ContentSource::Root => {
+            outputs.iter()
+                   .map(|&(_, _, area)| area)
+                   .fold(None, |total, area| match total {
+                       None => Some(area),
+                       Some(total) => Some(union(total, area))
+                   })
+        }
+        ContentSource::Output(ref name) => {
+            outputs.iter()
+                   .find(|&&(_, ref output_name, _)| output_name == name)
+                   .map(|&(_, _, area)| area)
+        }
+        ContentSource::View(id) => {
+            match views.iter_mut().find(|view| view.id == id) {
+                Some(view) => {
+                    let (width, height) = run_handles!([(surface: {view.shell.surface()})] => {
+                        surface.current_state().size()
+                    })?;
+                    Some(Area::new(view.surface_origin(), Size::new(width, height)))
+                }
+                None => None
+            }
+        }
+    };
+    let area = match area {
+        Some(area) => area,
+        None => return Ok(None)
+    };
+    let Size { width, height } = area.size;
+    if width <= 0 || height <= 0 {
+        return Ok(None)
+    }
+    // Views are drawn on their own, so any output will do.
+    let outputs: Vec<OutputHandle> = match source {
+        ContentSource::View(_) => outputs.into_iter().map(|(output, ..)| output).take(1).collect(),
+        _ => {
+            outputs.into_iter()
+                   .filter(|&(_, _, output_area)| intersection(area, output_area).is_some())
+                   .map(|(output, ..)| output)
+                   .collect()
+        }
+    };
+    if outputs.is_empty() {
+        return Ok(None)
+    }
+    let content = Content { width,
+                            height,
+                            data: vec![0; (width * height * 4) as usize],
+                            fresh: true };
+    Ok(Some(ContentRequest { source,
+                             area,
+                             content,
+                             outputs }))
+}
+
+/// Capture the views that are waiting for the output being rendered.
+///
+/// This draws over the output, so it must be done before the output is
+/// rendered.
+pub fn capture_views(requests: &mut [ContentRequest], renderer: &mut Renderer, views: &mut [View]) {
+    let output = renderer.output.weak_reference();
+    let (width, height) = renderer.output.effective_resolution();
+    let output_area = Area::new(Origin::default(), Size::new(width, height));
+    for request in requests.iter_mut() {
+        let view_id = match request.source {
+            ContentSource::View(view_id) => view_id,
+            _ => continue
+        };
+        if !request.outputs.contains(&output) {
+            continue
+        }
+        request.outputs.retain(|other| *other != output);
+        let view = match views.iter_mut().find(|view| view.id == view_id) {
+            Some(view) => view,
+            None => continue
+        };
+        let area = Area::new(Origin::default(), request.area.size);
+        let visible = match intersection(area, output_area) {
+            Some(visible) => visible,
+            None => continue
+        };
+        let content = &mut request.content;
+        let captured = run_handles!([(surface: {view.shell.surface()})] => {
+            renderer.clear([0.0, 0.0, 0.0, 0.0]);
+            let transform = renderer.output.get_transform().invert();
+            let matrix = project_box(area, transform, 0.0, renderer.output.transform_matrix());
+            renderer.render_texture_with_matrix(&surface.texture(), matrix);
+            read_area(renderer, content, area, visible, Origin::default())
+        });
+        match captured {
+            Ok(true) => {}
+            _ => warn!("Could not capture the content of view {}", view_id)
+        }
+    }
+}
+
+/// Capture what is rendered on the output for the requests waiting for it.
+pub fn capture_output(requests: &mut [ContentRequest],
+                      renderer: &mut Renderer,
+                      layout: &mut OutputLayoutHandle)
+                      -> HandleResult<()> {
+    let output = renderer.output.weak_reference();
+    let origin = run_handles!([(layout: {layout})] => {
+        layout.outputs()
+              .into_iter()
+              .find(|&(ref other, _)| *other == output)
+              .map(|(_, origin)| origin)
+    })?;
+    let origin = match origin {
+        Some(origin) => origin,
+        None => return Ok(())
+    };
+    let (width, height) = renderer.output.effective_resolution();
+    let output_area = Area::new(origin, Size::new(width, height));
+    for request in requests.iter_mut() {
+        if let ContentSource::View(_) = request.source {
+            continue
+        }
+        if !request.outputs.contains(&output) {
+            continue
+        }
+        request.outputs.retain(|other| *other != output);
+        let visible = match intersection(request.area, output_area) {
+            Some(visible) => visible,
+            None => continue
+        };
+        if !read_area(renderer, &mut request.content, request.area, visible, origin) {
+            warn!("Could not capture the content of {:?}", request.source);
+        }
+    }
+    Ok(())
+}
+
+/// Hand the requests that were filled in over to Lua.
+pub fn finish_content(requests: &mut Vec<ContentRequest>) {
+    if !requests.iter().any(|request| request.outputs.is_empty()) {
+        return
+    }
+    {
+        let mut contents = awesome::CONTENTS.lock().expect("Contents was poisoned");
+        let mut index = 0;
+        while index < requests.len() {
+            if !requests[index].outputs.is_empty() {
+                index += 1;
+                continue
+            }
+            let ContentRequest { source, content, .. } = requests.remove(index);
+            contents.retain(|&(ref other, _)| *other != source);
+            contents.push((source, content));
+        }
+    }
+    // Lua can't be called while the output is being rendered.
+    compositor::run_later(|_| awesome::content_changed());
+}
+
+/// Stop waiting for the output in the requests, as it's gone.
+pub fn content_output_destroyed(requests: &mut [ContentRequest], output: &wlroots::Output) {
+    let output = output.weak_reference();
+    for request in requests {
+        request.outputs.retain(|other| *other != output);
+    }
+}
+
+/// Read the visible part of the area from the output at the origin into the
+/// content of the area.
+fn read_area(renderer: &mut Renderer,
+             content: &mut Content,
+             area: Area,
+             visible: Area,
+             origin: Origin)
+             -> bool {
+    renderer.read_pixels(WL_SHM_FORMAT_ARGB8888,
+                         content.width as u32 * 4,
+                         visible.size.width as u32,
+                         visible.size.height as u32,
+                         (visible.origin.x - origin.x) as u32,
+                         (visible.origin.y - origin.y) as u32,
+                         (visible.origin.x - area.origin.x) as u32,
+                         (visible.origin.y - area.origin.y) as u32,
+                         &mut content.data[..])
+}
+
+/// The smallest area that contains both areas.
+fn union(a: Area, b: Area) -> Area {
+    let x = a.origin.x.min(b.origin.x);
+    let y = a.origin.y.min(b.origin.y);
+    let right = (a.origin.x + a.size.width).max(b.origin.x + b.size.width);
+    let bottom = (a.origin.y + a.size.height).max(b.origin.y + b.size.height);
+    Area::new(Origin::new(x, y), Size::new(right - x, bottom - y))
+}
+
+/// The area both areas cover, if they overlap.
+fn intersection(a: Area, b: Area) -> Option<Area> {
+    let x = a.origin.x.max(b.origin.x);
+    let y = a.origin.y.max(b.origin.y);
+    let right = (a.origin.x + a.size.width).min(b.origin.x + b.size.width);
+    let bottom = (a.origin.y + a.size.height).min(b.origin.y + b.size.height);
+    if right <= x || bottom <= y {
+        return None
+    }
+    Some(Area::new(Origin::new(x, y), Size::new(right - x, bottom - y)))
+}
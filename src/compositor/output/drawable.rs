@@ -0,0 +1,57 @@
+//! What Lua drew in drawables, e.g titlebars, as textures.
+//!
+//! Lua hands over the pixels of a drawable when it's refreshed. They are
+//! uploaded the next time an output is rendered, and that texture is drawn
+//! until the drawable is refreshed again.
+
+use compositor::{self, Server};
+use ipc::Content;
+use wlroots::{Renderer, Texture, WL_SHM_FORMAT_ARGB8888};
+
+#[derive(Debug)]
+pub struct DrawableTexture {
+    /// The id of the drawable in Lua.
+    id: usize,
+    /// What Lua drew since the texture was uploaded.
+    pixels: Option<Content>,
+    texture: Option<Texture<'static>>
+}
+
+/// Replace what is drawn for the drawable with the id with the pixels, or
+/// stop drawing it if there are none, e.g because it was resized.
+pub fn drawable_refreshed(id: usize, pixels: Option<Content>) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        forget_drawable(&mut server.drawables, id);
+        if let Some(pixels) = pixels {
+            server.drawables.push(DrawableTexture { id,
+                                                    pixels: Some(pixels),
+                                                    texture: None });
+        }
+    });
+}
+
+/// Stop drawing the drawable with the id, freeing its texture.
+pub fn forget_drawable(drawables: &mut Vec<DrawableTexture>, id: usize) {
+    drawables.retain(|drawable| drawable.id != id);
+}
+
+/// Get the texture of the drawable with the id, uploading what Lua drew in
+/// it first if that changed.
+pub fn drawable_texture<'drawables>(drawables: &'drawables mut [DrawableTexture],
+                                    renderer: &mut Renderer,
+                                    id: usize)
+                                    -> Option<&'drawables Texture<'static>> {
+    let drawable = drawables.iter_mut().find(|drawable| drawable.id == id)?;
+    if let Some(pixels) = drawable.pixels.take() {
+        drawable.texture = renderer.create_texture_from_pixels(WL_SHM_FORMAT_ARGB8888,
+                                                               pixels.width as u32 * 4,
+                                                               pixels.width as u32,
+                                                               pixels.height as u32,
+                                                               &pixels.data[..]);
+        if drawable.texture.is_none() {
+            warn!("Could not upload drawable {}", id);
+        }
+    }
+    drawable.texture.as_ref()
+}
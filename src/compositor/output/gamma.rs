@@ -0,0 +1,213 @@
+//! Gamma control, which night light tools use to tint the outputs.
+//!
+//! The gamma ramps an output had before anything changed them are kept, so
+//! they can be restored once the client that changed them goes away, Lua
+//! resets them, or the compositor exits.
+//!
+//! A client controlling the gamma of an output wins over Lua. What Lua sets
+//! meanwhile is kept and applied once the client goes away.
+
+use compositor::{self, Server};
+use wlroots::{self, Compositor, GammaControl, GammaControlHandle, GammaControlHandler,
+              OutputHandle};
+
+/// The gamma lookup tables of an output, one for each channel.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GammaRamps {
+    pub red: Vec<u16>,
+    pub green: Vec<u16>,
+    pub blue: Vec<u16>
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GammaState {
+    /// The ramps of outputs from before their gamma was changed.
+    original: Vec<(OutputHandle, GammaRamps)>,
+    /// The clients controlling the gamma of an output.
+    controls: Vec<(GammaControlHandle, OutputHandle)>,
+    /// The ramps Lua set for outputs.
+    lua: Vec<(OutputHandle, GammaRamps)>
+}
+
+#[derive(Debug, Default)]
+pub struct GammaControlManager;
+
+impl GammaRamps {
+    /// Linear ramps of the size, scaled per channel and then corrected by
+    /// the gamma.
+    ///
+    /// The factors are clamped between 0 and 1.
+    pub fn new(size: usize, (red, green, blue): (f64, f64, f64), gamma: f64) -> Self {
+        let ramp = |factor: f64| -> Vec<u16> {
+            let factor = factor.max(0.0).min(1.0);
+            (0..size).map(|index| {
+                         let value = index as f64 / (size.max(2) - 1) as f64;
+                         (value.powf(1.0 / gamma) * factor * u16::max_value() as f64) as u16
+                     })
+                     .collect()
+        };
+        GammaRamps { red: ramp(red),
+                     green: ramp(green),
+                     blue: ramp(blue) }
+    }
+}
+
+impl GammaControlManager {
+    pub fn new() -> Self {
+        GammaControlManager::default()
+    }
+}
+
+impl GammaControlHandler for GammaControlManager {
+    fn gamma_set(&mut self,
+                 compositor: &mut Compositor,
+                 control: &mut GammaControl,
+                 red: &[u16],
+                 green: &[u16],
+                 blue: &[u16]) {
+        let server: &mut Server = compositor.into();
+        let gamma = &mut server.gamma;
+        let output = control.output();
+        let weak_reference = control.weak_reference();
+        if !gamma.controls.iter().any(|&(ref other, _)| *other == weak_reference) {
+            gamma.controls.push((weak_reference, output.clone()));
+        }
+        let ramps = GammaRamps { red: red.to_vec(),
+                                 green: green.to_vec(),
+                                 blue: blue.to_vec() };
+        set_output_gamma(gamma, output, ramps);
+    }
+
+    fn control_destroyed(&mut self, compositor: &mut Compositor, control: &mut GammaControl) {
+        let server: &mut Server = compositor.into();
+        let gamma = &mut server.gamma;
+        let weak_reference = control.weak_reference();
+        let index = match gamma.controls
+                               .iter()
+                               .position(|&(ref other, _)| *other == weak_reference)
+        {
+            Some(index) => index,
+            None => return
+        };
+        let (_, output) = gamma.controls.remove(index);
+        let lua_ramps = gamma.lua
+                             .iter()
+                             .find(|&&(ref other, _)| *other == output)
+                             .map(|&(_, ref ramps)| ramps.clone());
+        match lua_ramps {
+            Some(ramps) => set_output_gamma(gamma, output, ramps),
+            None => restore_output_gamma(gamma, output)
+        }
+    }
+}
+
+/// Set the gamma ramps of the output, remembering the ramps it had before.
+pub fn set_output_gamma(gamma: &mut GammaState,
+                        mut output: OutputHandle,
+                        mut ramps: GammaRamps) {
+    if !gamma.original.iter().any(|&(ref other, _)| *other == output) {
+        let original = run_handles!([(output: {&mut output})] => {
+            output.get_gamma()
+        }).ok().and_then(|original| original);
+        if let Some((red, green, blue)) = original {
+            gamma.original.push((output.clone(), GammaRamps { red, green, blue }));
+        }
+    }
+    run_handles!([(output: {&mut output})] => {
+        if ramps.red.len() == output.gamma_size() as usize {
+            output.set_gamma(&mut ramps.red, &mut ramps.green, &mut ramps.blue);
+        } else {
+            warn!("Gamma ramps don't fit {}", output.name());
+        }
+    }).unwrap_or_else(|_| warn!("Could not set gamma, output was destroyed"));
+}
+
+/// Put back the ramps the output had before its gamma was changed.
+pub fn restore_output_gamma(gamma: &mut GammaState, mut output: OutputHandle) {
+    let index = match gamma.original.iter().position(|&(ref other, _)| *other == output) {
+        Some(index) => index,
+        None => return
+    };
+    let (_, mut ramps) = gamma.original.remove(index);
+    run_handles!([(output: {&mut output})] => {
+        output.set_gamma(&mut ramps.red, &mut ramps.green, &mut ramps.blue);
+    }).unwrap_or_else(|_| warn!("Could not restore gamma, output was destroyed"));
+}
+
+/// Set the gamma of the output with the name from per channel factors and a
+/// gamma correction, or restore its original gamma if there are none.
+///
+/// If a client controls the gamma of the output, this is only applied once
+/// the client goes away.
+pub fn set_gamma(output_name: String, factors: Option<((f64, f64, f64), f64)>) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        let Server { ref mut layout,
+                     ref mut gamma,
+                     .. } = *server;
+        let output = run_handles!([(layout: {layout})] => {
+            layout.outputs().into_iter().map(|(output, _)| output).find(|output| {
+                output.clone().run(|output| output.name() == output_name).unwrap_or(false)
+            })
+        });
+        let mut output = match output {
+            Ok(Some(output)) => output,
+            Ok(None) => {
+                warn!("Can't set the gamma of {}, there is no such output", output_name);
+                return
+            }
+            Err(_) => {
+                warn!("Can't set the gamma of {}, the output layout was destroyed", output_name);
+                return
+            }
+        };
+        let ramps = match factors {
+            Some((factors, correction)) => {
+                let size = run_handles!([(output: {&mut output})] => {
+                    output.gamma_size()
+                });
+                let size = match size {
+                    Ok(size) => size,
+                    Err(_) => {
+                        warn!("Can't set the gamma of {}, it was destroyed", output_name);
+                        return
+                    }
+                };
+                if size == 0 {
+                    warn!("{} does not support gamma", output_name);
+                    return
+                }
+                Some(GammaRamps::new(size as usize, factors, correction))
+            }
+            None => None
+        };
+        gamma.lua.retain(|&(ref other, _)| *other != output);
+        if let Some(ref ramps) = ramps {
+            gamma.lua.push((output.clone(), ramps.clone()));
+        }
+        if gamma.controls.iter().any(|&(_, ref other)| *other == output) {
+            info!("A client controls the gamma of {}, it's set once the client is done",
+                  output_name);
+            return
+        }
+        match ramps {
+            Some(ramps) => set_output_gamma(gamma, output, ramps),
+            None => restore_output_gamma(gamma, output)
+        }
+    });
+}
+
+/// Restore the gamma of an output that is going away, e.g because the
+/// compositor is exiting.
+pub fn output_destroyed(gamma: &mut GammaState, output: &mut wlroots::Output) {
+    let weak_reference = output.weak_reference();
+    gamma.controls.retain(|&(_, ref other)| *other != weak_reference);
+    gamma.lua.retain(|&(ref other, _)| *other != weak_reference);
+    if let Some(index) = gamma.original
+                              .iter()
+                              .position(|&(ref other, _)| *other == weak_reference)
+    {
+        let (_, mut ramps) = gamma.original.remove(index);
+        output.set_gamma(&mut ramps.red, &mut ramps.green, &mut ramps.blue);
+    }
+}
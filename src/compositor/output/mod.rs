@@ -1,7 +1,15 @@
+mod content;
+mod drawable;
+mod gamma;
 mod output_manager;
 mod output;
 mod output_layout_manager;
+mod screencopy;
 
+pub use self::content::*;
+pub use self::drawable::*;
+pub use self::gamma::*;
 pub use self::output::*;
 pub use self::output_layout_manager::*;
 pub use self::output_manager::*;
+pub use self::screencopy::*;
@@ -1,6 +1,8 @@
-use compositor::Server;
+use awesome;
+use compositor::{self, DrawableTexture, LockState, Server, TitlebarSide, View};
 use std::time::{SystemTime, UNIX_EPOCH};
-use wlroots::{self, project_box, Area, Compositor, OutputHandler, Size};
+use wlroots::{self, project_box, Area, Compositor, HandleResult, Origin, OutputHandler,
+              OutputLayoutHandle, Renderer, Size, Texture};
 
 pub struct Output;
 
@@ -9,36 +11,219 @@ impl OutputHandler for Output {
         let state: &mut Server = compositor.data.downcast_mut().unwrap();
         let Server { ref mut layout,
                      ref mut views,
+                     ref mut lock,
+                     ref mut screencopy_frames,
+                     ref mut content_requests,
+                     ref mut drawables,
+                     ref mut cursor,
+                     ref mut seat,
                      .. } = *state;
-        let renderer = compositor.renderer.as_mut().expect("gles2 disabled");
-        let mut renderer = renderer.render(output, None);
-        renderer.clear([0.25, 0.25, 0.25, 1.0]);
-        for view in views {
-            let mut surface = view.shell.surface();
-            run_handles!([(surface: {surface}),
-                          (layout: {&mut *layout})] => {
-                let (width, height) = surface.current_state().size();
-                let (render_width, render_height) =
-                    (width * renderer.output.scale() as i32,
-                     height * renderer.output.scale() as i32);
-                let render_box = Area::new(view.origin,
-                                           Size::new(render_width,
-                                                     render_height));
-                if layout.intersects(renderer.output, render_box) {
-                    let transform = renderer.output.get_transform().invert();
-                    let matrix = project_box(render_box,
-                                             transform,
-                                             0.0,
-                                             renderer.output
-                                             .transform_matrix());
-                    renderer.render_texture_with_matrix(&surface.texture(),
-                                                        matrix);
-                    let start = SystemTime::now();
-                    let now = start.duration_since(UNIX_EPOCH)
-                        .expect("Time went backwards");
-                    surface.send_frame_done(now);
-                }
-            }).expect("Could not render views")
+        // The cursor is usually drawn by the hardware, which screencopy can't
+        // see.
+        let copy_cursor = compositor::wants_cursor(screencopy_frames, output);
+        if copy_cursor {
+            output.lock_software_cursors(true);
+        }
+        {
+            let renderer = compositor.renderer.as_mut().expect("gles2 disabled");
+            let mut renderer = renderer.render(output, None);
+            if !lock.locked {
+                compositor::capture_views(content_requests, &mut renderer, views);
+            }
+            render_output(&mut renderer, layout, views, drawables, lock);
+            compositor::capture_output(content_requests, &mut renderer, layout)
+                .expect("Could not capture content");
+            compositor::render_drag_icon(&mut renderer, layout, cursor, seat)
+                .expect("Could not render drag icon");
+            compositor::copy_frames(screencopy_frames, &mut renderer, false);
+            renderer.render_software_cursors();
+            if copy_cursor {
+                compositor::copy_frames(screencopy_frames, &mut renderer, true);
+                renderer.output.lock_software_cursors(false);
+            }
+        }
+        compositor::finish_content(content_requests);
+        // The frame is only shown once the renderer is done with it.
+        compositor::locked_frame_presented(lock, layout, output.weak_reference())
+            .expect("Could not tell the lock client the session is locked")
+    }
+
+    fn destroyed(&mut self, compositor: &mut Compositor, output: &mut wlroots::Output) {
+        let server: &mut Server = compositor.into();
+        compositor::output_destroyed(&mut server.gamma, output);
+        compositor::content_output_destroyed(&mut server.content_requests, output);
+    }
+}
+
+/// Draw what belongs on the output, which is only the lock surface while
+/// the session is locked.
+pub fn render_output(renderer: &mut Renderer,
+                     layout: &mut OutputLayoutHandle,
+                     views: &mut [View],
+                     drawables: &mut [DrawableTexture],
+                     lock: &mut LockState) {
+    if lock.locked {
+        render_lock_surface(renderer, lock);
+    } else {
+        render_views(renderer, layout, views, drawables);
+    }
+}
+
+/// Draw the lock surface of the output.
+fn render_lock_surface(renderer: &mut Renderer, lock: &mut LockState) {
+    // Outputs stay blank until their lock surface is ready.
+    renderer.clear([0.0, 0.0, 0.0, 1.0]);
+    let output_handle = renderer.output.weak_reference();
+    if let Some(lock_surface) = lock.surfaces
+                                    .iter_mut()
+                                    .find(|surface| surface.output == output_handle)
+    {
+        let surface = match run_handles!([(lock_surface: {&mut lock_surface.handle})] => {
+            lock_surface.surface()
+        }) {
+            Ok(surface) => surface,
+            Err(_) => {
+                warn!("Lock surface was destroyed, leaving the output blank");
+                return
+            }
+        };
+        run_handles!([(surface: {surface})] => {
+            let (width, height) = surface.current_state().size();
+            let scale = renderer.output.scale() as i32;
+            let render_box = Area::new(Origin::default(),
+                                       Size::new(width * scale, height * scale));
+            let transform = renderer.output.get_transform().invert();
+            let matrix = project_box(render_box,
+                                     transform,
+                                     0.0,
+                                     renderer.output.transform_matrix());
+            renderer.render_texture_with_matrix(&surface.texture(), matrix);
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)
+                                       .expect("Time went backwards");
+            surface.send_frame_done(now);
+        }).unwrap_or_else(|_| warn!("Could not render lock surface, it was destroyed"))
+    }
+}
+
+/// Draw the views that are on the output.
+fn render_views(renderer: &mut Renderer,
+                layout: &mut OutputLayoutHandle,
+                views: &mut [View],
+                drawables: &mut [DrawableTexture]) {
+    renderer.clear([0.25, 0.25, 0.25, 1.0]);
+    for view in views {
+        let mut surface = view.shell.surface();
+        run_handles!([(surface: {surface}),
+                      (layout: {&mut *layout})] => {
+            let (width, height) = surface.current_state().size();
+            let (render_width, render_height) =
+                (width * renderer.output.scale() as i32,
+                 height * renderer.output.scale() as i32);
+            let render_box = Area::new(view.surface_origin(),
+                                       Size::new(render_width,
+                                                 render_height));
+            if layout.intersects(renderer.output, render_box) {
+                let transform = renderer.output.get_transform().invert();
+                let matrix = project_box(render_box,
+                                         transform,
+                                         0.0,
+                                         renderer.output
+                                         .transform_matrix());
+                renderer.render_texture_with_alpha(&surface.texture(),
+                                                   matrix,
+                                                   view.opacity);
+                let start = SystemTime::now();
+                let now = start.duration_since(UNIX_EPOCH)
+                    .expect("Time went backwards");
+                surface.send_frame_done(now);
+            }
+        }).expect("Could not render views");
+        render_titlebars(renderer, layout, view, drawables).expect("Could not render titlebars");
+        render_border(renderer, layout, view).expect("Could not render border")
+    }
+    // Drawins are drawn above the views.
+    render_drawins(renderer, layout, drawables).expect("Could not render drawins")
+}
+
+/// Draw what Lua drew in the visible drawins.
+fn render_drawins(renderer: &mut Renderer,
+                  layout: &mut OutputLayoutHandle,
+                  drawables: &mut [DrawableTexture])
+                  -> HandleResult<()> {
+    for (area, opacity, id) in awesome::drawin_contents() {
+        let visible = run_handles!([(layout: {&mut *layout})] => {
+            layout.intersects(renderer.output, area)
+        })?;
+        if !visible {
+            continue
+        }
+        if let Some(texture) = compositor::drawable_texture(drawables, renderer, id) {
+            render_texture(renderer, texture, area, opacity);
+        }
+    }
+    Ok(())
+}
+
+/// Draw the texture at the area in the output layout.
+fn render_texture(renderer: &mut Renderer, texture: &Texture, area: Area, alpha: f32) {
+    let transform = renderer.output.get_transform().invert();
+    let matrix = project_box(area, transform, 0.0, renderer.output.transform_matrix());
+    renderer.render_texture_with_alpha(texture, matrix, alpha);
+}
+
+/// Draw the border around the view and its titlebars.
+fn render_border(renderer: &mut Renderer,
+                 layout: &mut OutputLayoutHandle,
+                 view: &mut View)
+                 -> HandleResult<()> {
+    let border = view.decorations.border_width;
+    if border <= 0 {
+        return Ok(())
+    }
+    let size = view.client_size()?;
+    let mut color = view.decorations.border_color();
+    color[3] *= view.opacity;
+    let Origin { x, y } = view.origin;
+    run_handles!([(layout: {layout})] => {
+        for side in view.decorations.border_areas(size).iter() {
+            let side = Area::new(Origin::new(x + side.origin.x, y + side.origin.y), side.size);
+            if side.size.width > 0 && side.size.height > 0
+               && layout.intersects(renderer.output, side)
+            {
+                let matrix = renderer.output.transform_matrix();
+                renderer.render_colored_rect(side, color, matrix);
+            }
+        }
+    })
+}
+
+/// Draw the titlebars Lua drew for the view, if the compositor decorates it.
+fn render_titlebars(renderer: &mut Renderer,
+                    layout: &mut OutputLayoutHandle,
+                    view: &mut View,
+                    drawables: &mut [DrawableTexture])
+                    -> HandleResult<()> {
+    if !view.decorations.server_side() {
+        return Ok(())
+    }
+    for &side in TitlebarSide::all().iter() {
+        let index = side as usize;
+        let Area { origin, size } = view.decorations.titlebar_areas[index];
+        let id = match view.decorations.titlebar_drawables[index] {
+            Some(id) if size.width > 0 && size.height > 0 => id,
+            _ => continue
+        };
+        let area = Area::new(Origin::new(view.origin.x + origin.x, view.origin.y + origin.y),
+                             size);
+        let visible = run_handles!([(layout: {&mut *layout})] => {
+            layout.intersects(renderer.output, area)
+        })?;
+        if !visible {
+            continue
+        }
+        if let Some(texture) = compositor::drawable_texture(drawables, renderer, id) {
+            render_texture(renderer, texture, area, view.opacity);
         }
     }
+    Ok(())
 }
@@ -1,4 +1,4 @@
-use compositor::{Output, Server};
+use compositor::{self, Output, Server};
 use wlroots::{Compositor, OutputBuilder, OutputBuilderResult, OutputManagerHandler};
 
 pub struct OutputManager;
@@ -18,17 +18,21 @@ impl OutputManagerHandler for OutputManager {
         let res = builder.build_best_mode(Output);
         let Server { ref mut cursor,
                      ref mut layout,
-                     ref mut xcursor_theme,
+                     ref mut cursor_theme,
                      .. } = *server;
-        run_handles!([(layout: {layout}), (cursor: {cursor})] => {
-            let xcursor = xcursor_theme.get_cursor("left_ptr".into())
-                .expect("Could not load left_ptr cursor");
+        cursor_theme.load_scale(res.output.scale());
+        run_handles!([(layout: {layout}), (cursor: {&mut *cursor})] => {
             layout.add_auto(res.output);
             cursor.attach_output_layout(layout);
-            cursor.set_cursor_image(&xcursor.images()[0]);
             let (x, y) = cursor.coords();
             cursor.warp(None, x, y);
         }).expect("Could not setup output with cursor and layout");
+        cursor_theme.reset_image(cursor).expect("Cursor was destroyed");
+        // Devices can be mapped to the output once it's done being set up.
+        compositor::run_later(|compositor| {
+                                  let server: &mut Server = compositor.into();
+                                  compositor::map_devices_to_outputs(server)
+                              });
         Some(res)
     }
 }
@@ -0,0 +1,94 @@
+//! Screencopy, which screenshot and recording tools use to capture an
+//! output or a region of one.
+//!
+//! Frames that clients request are copied into their shm buffers the next
+//! time the output is rendered.
+
+use compositor::Server;
+use std::time::{SystemTime, UNIX_EPOCH};
+use wlroots::{self, Area, Compositor, Origin, Renderer, ScreencopyFrame, ScreencopyFrameHandle,
+              ScreencopyHandler, Size};
+
+#[derive(Debug, Default)]
+pub struct ScreencopyManager;
+
+impl ScreencopyManager {
+    pub fn new() -> Self {
+        ScreencopyManager::default()
+    }
+}
+
+impl ScreencopyHandler for ScreencopyManager {
+    fn frame_requested(&mut self, compositor: &mut Compositor, frame: &mut ScreencopyFrame) {
+        let server: &mut Server = compositor.into();
+        server.screencopy_frames.push(frame.weak_reference());
+    }
+
+    fn frame_destroyed(&mut self, compositor: &mut Compositor, frame: &mut ScreencopyFrame) {
+        let server: &mut Server = compositor.into();
+        let weak_reference = frame.weak_reference();
+        server.screencopy_frames.retain(|frame| *frame != weak_reference);
+    }
+}
+
+/// If a frame wants the cursor on the output being rendered.
+///
+/// The cursor must then be drawn in software, otherwise it isn't part of
+/// what is rendered.
+pub fn wants_cursor(frames: &mut [ScreencopyFrameHandle], output: &wlroots::Output) -> bool {
+    let output = output.weak_reference();
+    frames.iter_mut().any(|frame| {
+                              frame.run(|frame| frame.output() == output && frame.overlay_cursor())
+                                   .unwrap_or(false)
+                          })
+}
+
+/// Copy what was rendered into the frames of the output that do or don't
+/// want the cursor, and let their clients know.
+///
+/// Copied frames are done and are removed.
+pub fn copy_frames(frames: &mut Vec<ScreencopyFrameHandle>,
+                   renderer: &mut Renderer,
+                   with_cursor: bool) {
+    let output = renderer.output.weak_reference();
+    let mut index = 0;
+    while index < frames.len() {
+        let copied = run_handles!([(frame: {&mut frames[index]})] => {
+            if frame.output() == output && frame.overlay_cursor() == with_cursor {
+                copy_frame(frame, renderer);
+                true
+            } else {
+                false
+            }
+        }).unwrap_or(true);
+        if copied {
+            frames.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+}
+
+fn copy_frame(frame: &mut ScreencopyFrame, renderer: &mut Renderer) {
+    let Area { origin: Origin { x, y },
+               size: Size { width, height } } = frame.area();
+    let read = frame.with_shm_buffer(|buffer| {
+                                         renderer.read_pixels(buffer.format(),
+                                                              buffer.stride(),
+                                                              width as u32,
+                                                              height as u32,
+                                                              x as u32,
+                                                              y as u32,
+                                                              0,
+                                                              0,
+                                                              buffer.data_mut())
+                                     });
+    if read == Some(true) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)
+                                   .expect("Time went backwards");
+        frame.send_ready(now);
+    } else {
+        warn!("Could not copy output into screencopy buffer");
+        frame.send_failed();
+    }
+}
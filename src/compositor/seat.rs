@@ -1,5 +1,7 @@
-use compositor::View;
-use wlroots::{Origin, SeatHandle, SeatHandler};
+use compositor::{self, Drag, SelectionKind, Server, TitlebarSide, TouchPoint, View};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use wlroots::{self, Compositor, Origin, PointerConstraintHandle, SeatHandle, SeatHandler,
+              seat_events::SetCursorEvent};
 
 #[derive(Debug, Default)]
 pub struct SeatManager;
@@ -12,12 +14,79 @@ pub enum Action {
     Moving { start: Origin }
 }
 
-#[derive(Debug, Default, Clone, Eq, PartialEq)]
+/// How the keyboard focus follows the pointer.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FocusPolicy {
+    /// Views are only focused when they are clicked.
+    ClickToFocus,
+    /// Views are focused when the pointer enters them.
+    ///
+    /// Focus is kept when the pointer leaves to the background.
+    Sloppy,
+    /// Views are focused when the pointer enters them.
+    ///
+    /// Focus is cleared when the pointer leaves to the background.
+    FollowsMouse
+}
+
+impl Default for FocusPolicy {
+    fn default() -> Self {
+        FocusPolicy::ClickToFocus
+    }
+}
+
+impl FocusPolicy {
+    pub fn from_str(policy: &str) -> Option<Self> {
+        match policy {
+            "click" => Some(FocusPolicy::ClickToFocus),
+            "sloppy" => Some(FocusPolicy::Sloppy),
+            "mouse" => Some(FocusPolicy::FollowsMouse),
+            _ => None
+        }
+    }
+}
+
+/// A touchpad gesture that is in progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    /// How far the fingers have moved since the swipe began.
+    Swipe { fingers: u32, dx: f64, dy: f64 },
+    /// The latest scale and rotation of the pinch, which are relative to
+    /// where it began.
+    Pinch { fingers: u32, scale: f64, rotation: f64 }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Seat {
     pub seat: SeatHandle,
     pub focused: Option<View>,
     pub action: Option<Action>,
-    pub meta: bool
+    pub meta: bool,
+    /// The view the pointer is currently over.
+    pub hovered: Option<View>,
+    /// The titlebar the pointer is currently over, and the id of its view.
+    pub hovered_titlebar: Option<(usize, TitlebarSide)>,
+    pub focus_policy: FocusPolicy,
+    /// How long the pointer has to rest on a view before it is focused.
+    pub focus_delay: Duration,
+    /// Bumped every time the hovered view changes, so that delayed focus
+    /// changes can tell if they are stale.
+    pub hover_serial: u32,
+    /// The touch point that is acting as the pointer, because it went down
+    /// on a drawin.
+    pub touch_emulation: Option<i32>,
+    /// The touch points that went down on a surface.
+    pub touch_points: Vec<TouchPoint>,
+    pub gesture: Option<Gesture>,
+    /// The pointer constraints that clients requested.
+    pub constraints: Vec<PointerConstraintHandle>,
+    /// The constraint of the focused surface, if it hasn't been released.
+    pub active_constraint: Option<PointerConstraintHandle>,
+    /// The constraint of the focused surface that confines the pointer to a
+    /// region, waiting for the pointer to enter that region.
+    pub pending_constraint: Option<PointerConstraintHandle>,
+    /// The drag and drop the pointer is doing, if any.
+    pub drag: Option<Drag>
 }
 
 impl Seat {
@@ -28,10 +97,102 @@ impl Seat {
     }
 }
 
-impl SeatHandler for SeatManager {}
+impl SeatHandler for SeatManager {
+    fn cursor_set(&mut self,
+                  compositor: &mut Compositor,
+                  seat: &mut wlroots::Seat,
+                  event: &SetCursorEvent) {
+        // Only the client that has pointer focus may set the cursor image.
+        match seat.pointer_focused_client() {
+            Some(ref client) if *client == event.seat_client() => {}
+            _ => return
+        }
+        let server: &mut Server = compositor.into();
+        let (hotspot_x, hotspot_y) = event.location();
+        run_handles!([(cursor: {&mut server.cursor})] => {
+            // No surface means the client wants the cursor hidden.
+            cursor.set_surface(event.surface(), hotspot_x, hotspot_y);
+        }).expect("Cursor was destroyed")
+    }
+
+    fn selection_set(&mut self, _: &mut Compositor, seat: &mut wlroots::Seat) {
+        match seat.selection_source() {
+            Some(source) => {
+                let mime_types = source.mime_types();
+                compositor::selection_changed(SelectionKind::Clipboard,
+                                              &mime_types,
+                                              |mime_type, fd| source.send(mime_type, fd))
+            }
+            None => compositor::selection_changed(SelectionKind::Clipboard, &[], |_, _| {})
+        }
+    }
+
+    fn primary_selection_set(&mut self, _: &mut Compositor, seat: &mut wlroots::Seat) {
+        match seat.primary_selection_source() {
+            Some(source) => {
+                let mime_types = source.mime_types();
+                compositor::selection_changed(SelectionKind::Primary,
+                                              &mime_types,
+                                              |mime_type, fd| source.send(mime_type, fd))
+            }
+            None => compositor::selection_changed(SelectionKind::Primary, &[], |_, _| {})
+        }
+    }
+
+    fn drag_started(&mut self,
+                    compositor: &mut Compositor,
+                    _: &mut wlroots::Seat,
+                    drag: &mut wlroots::Drag) {
+        compositor::drag_started(compositor, drag)
+    }
+
+    fn drag_source_destroyed(&mut self,
+                             compositor: &mut Compositor,
+                             _: &mut wlroots::Seat,
+                             _: &mut wlroots::Drag) {
+        // The client that was dragging went away, so there is nothing to drop.
+        let server: &mut Server = compositor.into();
+        let Server { ref mut seat,
+                     ref mut views,
+                     ref mut cursor,
+                     ref mut cursor_theme,
+                     ref mut keyboards,
+                     ref lock,
+                     .. } = *server;
+        compositor::cancel_drag(seat).expect("Could not cancel drag");
+        if lock.locked {
+            return
+        }
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)
+                                    .expect("Time went backwards");
+        compositor::update_hovered(seat,
+                                   &mut **keyboards,
+                                   views,
+                                   cursor,
+                                   cursor_theme,
+                                   time,
+                                   true).expect("Could not update the view under the pointer")
+    }
+
+    fn drag_destroyed(&mut self,
+                      compositor: &mut Compositor,
+                      _: &mut wlroots::Seat,
+                      drag: &mut wlroots::Drag) {
+        compositor::drag_destroyed(compositor, drag)
+    }
+}
 
 impl SeatManager {
     pub fn new() -> Self {
         SeatManager::default()
     }
 }
+
+/// Set how the keyboard focus follows the pointer.
+pub fn set_focus_policy(policy: FocusPolicy, delay: Duration) {
+    compositor::run_later(move |compositor| {
+                              let server: &mut Server = compositor.into();
+                              server.seat.focus_policy = policy;
+                              server.seat.focus_delay = delay;
+                          });
+}
@@ -0,0 +1,189 @@
+//! The clipboard and the primary selection, which clients set through the
+//! seat.
+//!
+//! Whenever a selection changes its text is read as the owner writes it,
+//! from the main loop, so Lua can get it without waiting on the client that
+//! owns it.
+
+use awesome;
+use glib::{self, IOCondition};
+use nix::fcntl::{self, FcntlArg, O_CLOEXEC, O_NONBLOCK};
+use nix::unistd;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::io::FromRawFd;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+/// The mime types of text, from most to least preferred.
+const TEXT_MIME_TYPES: [&'static str; 5] = ["text/plain;charset=utf-8",
+                                            "text/plain",
+                                            "UTF8_STRING",
+                                            "STRING",
+                                            "TEXT"];
+/// Selections bigger than this are cut off.
+const MAX_SELECTION_SIZE: usize = 1 << 20;
+/// How many seconds the owner of a selection has to write it.
+const SELECTION_TIMEOUT: u32 = 5;
+
+/// Counts how many times each selection changed, so a slow read doesn't
+/// overwrite a newer selection.
+static PRIMARY_SERIAL: AtomicUsize = ATOMIC_USIZE_INIT;
+static CLIPBOARD_SERIAL: AtomicUsize = ATOMIC_USIZE_INIT;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionKind {
+    Primary,
+    Clipboard
+}
+
+/// The text of a selection, as far as it was read.
+#[derive(Debug)]
+struct SelectionRead {
+    kind: SelectionKind,
+    serial: usize,
+    file: File,
+    data: Vec<u8>,
+    failed: bool
+}
+
+impl SelectionKind {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            SelectionKind::Primary => "primary",
+            SelectionKind::Clipboard => "clipboard"
+        }
+    }
+
+    fn serial(&self) -> &'static AtomicUsize {
+        match *self {
+            SelectionKind::Primary => &PRIMARY_SERIAL,
+            SelectionKind::Clipboard => &CLIPBOARD_SERIAL
+        }
+    }
+}
+
+/// Pick the mime type to read the selection as, if it has any text.
+pub fn text_mime_type(mime_types: &[String]) -> Option<String> {
+    TEXT_MIME_TYPES.iter()
+                   .find(|mime_type| mime_types.iter().any(|other| other == *mime_type))
+                   .map(|mime_type| mime_type.to_string())
+}
+
+/// Read the text of the new selection and give it to Lua.
+///
+/// `send` asks the owner of the selection to write the text as the mime
+/// type into the file descriptor. If there is no text, the selection is
+/// cleared.
+pub fn selection_changed<F>(kind: SelectionKind, mime_types: &[String], send: F)
+    where F: FnOnce(&str, i32)
+{
+    let serial = kind.serial().fetch_add(1, Ordering::SeqCst) + 1;
+    let mime_type = match text_mime_type(mime_types) {
+        Some(mime_type) => mime_type,
+        None => {
+            update_selection(kind, serial, None);
+            return
+        }
+    };
+    let (read_fd, write_fd) = match unistd::pipe2(O_CLOEXEC) {
+        Ok(fds) => fds,
+        Err(err) => {
+            warn!("Could not read the {} selection: {}", kind.as_str(), err);
+            return
+        }
+    };
+    let file = unsafe { File::from_raw_fd(read_fd) };
+    if let Err(err) = fcntl::fcntl(read_fd, FcntlArg::F_SETFL(O_NONBLOCK)) {
+        warn!("Could not read the {} selection: {}", kind.as_str(), err);
+        unistd::close(write_fd).ok();
+        return
+    }
+    // The owner gets the write end, and it is closed after sending it.
+    send(&mime_type, write_fd);
+    let read = Arc::new(Mutex::new(Some(SelectionRead { kind,
+                                                        serial,
+                                                        file,
+                                                        data: vec![],
+                                                        failed: false })));
+    let fd_read = read.clone();
+    let condition = IOCondition::IN | IOCondition::HUP | IOCondition::ERR;
+    let source = glib::source::unix_fd_add(read_fd, condition, move |_, _| {
+        let mut read = fd_read.lock().expect("Selection read was poisoned");
+        let done = match *read {
+            Some(ref mut read) => read.read_available(),
+            None => true
+        };
+        if !done {
+            return glib::Continue(true)
+        }
+        if let Some(read) = read.take() {
+            read.finish();
+        }
+        glib::Continue(false)
+    });
+    let mut source = Some(source);
+    glib::timeout_add_seconds(SELECTION_TIMEOUT, move || {
+        let read = read.lock().expect("Selection read was poisoned").take();
+        if let (Some(read), Some(source)) = (read, source.take()) {
+            warn!("The owner of the {} selection took too long to write it",
+                  read.kind.as_str());
+            glib::source::source_remove(source);
+            read.finish();
+        }
+        glib::Continue(false)
+    });
+}
+
+impl SelectionRead {
+    /// Read what the owner wrote so far, returning true once it's done.
+    fn read_available(&mut self) -> bool {
+        let mut buffer = [0; 4096];
+        loop {
+            match self.file.read(&mut buffer) {
+                Ok(0) => return true,
+                Ok(count) => {
+                    self.data.extend_from_slice(&buffer[..count]);
+                    if self.data.len() >= MAX_SELECTION_SIZE {
+                        self.data.truncate(MAX_SELECTION_SIZE);
+                        return true
+                    }
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return false,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    warn!("Could not read the {} selection: {}", self.kind.as_str(), err);
+                    self.failed = true;
+                    return true
+                }
+            }
+        }
+    }
+
+    /// Give what was read to Lua.
+    ///
+    /// A character cut off by the size limit is replaced, rather than losing
+    /// all of the text.
+    fn finish(self) {
+        let text = if self.failed {
+            None
+        } else {
+            Some(String::from_utf8_lossy(&self.data).into_owned())
+        };
+        update_selection(self.kind, self.serial, text);
+    }
+}
+
+fn update_selection(kind: SelectionKind, serial: usize, text: Option<String>) {
+    if kind.serial().load(Ordering::SeqCst) != serial {
+        return
+    }
+    {
+        let mut selection = awesome::SELECTION.lock().expect("Selection was poisoned");
+        match kind {
+            SelectionKind::Primary => selection.primary = text,
+            SelectionKind::Clipboard => selection.clipboard = text
+        }
+    }
+    awesome::selection_changed(kind.as_str());
+}
@@ -2,7 +2,7 @@ mod xdg_v6;
 
 pub use self::xdg_v6::*;
 
-use wlroots::{Area, HandleResult, SurfaceHandle, XdgV6ShellSurfaceHandle};
+use wlroots::{Area, HandleResult, Size, SurfaceHandle, XdgV6ShellState, XdgV6ShellSurfaceHandle};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Shell {
@@ -21,12 +21,46 @@ impl Shell {
         }
     }
 
+    /// Get the surface of the shell at the shell local coordinates.
+    ///
+    /// This could be a subsurface or a popup, so the coordinates returned are
+    /// local to the surface found.
+    pub fn surface_at(&mut self,
+                      sx: f64,
+                      sy: f64)
+                      -> HandleResult<Option<(SurfaceHandle, f64, f64)>> {
+        match *self {
+            Shell::XdgV6(ref mut shell) => {
+                shell.run(|shell| {
+                              let (mut sub_sx, mut sub_sy) = (0.0, 0.0);
+                              shell.surface_at(sx, sy, &mut sub_sx, &mut sub_sy)
+                                   .map(|surface| (surface, sub_sx, sub_sy))
+                          })
+            }
+        }
+    }
+
     /// Get the geometry of a shell.
     pub fn geometry(&mut self) -> HandleResult<Area> {
         match *self {
             Shell::XdgV6(ref mut shell) => shell.run(|shell| shell.geometry())
         }
     }
+
+    /// Ask the client of the shell to resize it.
+    pub fn set_size(&mut self, size: Size) -> HandleResult<()> {
+        match *self {
+            Shell::XdgV6(ref mut shell) => {
+                shell.run(|shell| {
+                              if let Some(&mut XdgV6ShellState::TopLevel(ref mut toplevel)) =
+                                  shell.state()
+                              {
+                                  toplevel.set_size(size.width as u32, size.height as u32);
+                              }
+                          })
+            }
+        }
+    }
 }
 
 impl Into<Shell> for XdgV6ShellSurfaceHandle {
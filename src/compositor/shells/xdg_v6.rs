@@ -1,6 +1,7 @@
-use compositor::{Server, Shell, View};
-use wlroots::{Compositor, XdgV6ShellHandler, XdgV6ShellManagerHandler, XdgV6ShellSurface,
-              XdgV6ShellSurfaceHandle};
+use awesome;
+use compositor::{self, Server, Shell, View};
+use wlroots::{Compositor, Surface, XdgV6ShellHandler, XdgV6ShellManagerHandler,
+              XdgV6ShellSurface, XdgV6ShellSurfaceHandle};
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct XdgV6 {
@@ -13,7 +14,20 @@ impl XdgV6 {
     }
 }
 
-impl XdgV6ShellHandler for XdgV6 {}
+impl XdgV6ShellHandler for XdgV6 {
+    fn on_commit(&mut self,
+                 compositor: &mut Compositor,
+                 _: &mut Surface,
+                 shell_surface: &mut XdgV6ShellSurface) {
+        let server: &mut Server = compositor.into();
+        let shell: Shell = shell_surface.weak_reference().into();
+        if let Some(view) = server.views.iter_mut().find(|view| view.shell == shell) {
+            // The titlebars follow the size of the surface.
+            compositor::update_titlebars(view)
+                .unwrap_or_else(|_| warn!("Could not update titlebars, view was destroyed"));
+        }
+    }
+}
 
 pub struct XdgV6ShellManager;
 
@@ -23,8 +37,9 @@ impl XdgV6ShellManagerHandler for XdgV6ShellManager {
                    shell_surface: &mut XdgV6ShellSurface)
                    -> Option<Box<XdgV6ShellHandler>> {
         let server: &mut Server = compositor.into();
-        server.views
-              .push(View::new(Shell::XdgV6(shell_surface.weak_reference().into())));
+        let view = View::new(Shell::XdgV6(shell_surface.weak_reference().into()));
+        awesome::manage_client(view.id);
+        server.views.push(view);
         Some(Box::new(XdgV6::new()))
     }
 
@@ -37,7 +52,13 @@ impl XdgV6ShellManagerHandler for XdgV6ShellManager {
                                  .iter()
                                  .position(|view| view.shell == destroyed_shell)
         {
-            server.views.remove(pos);
+            let view = server.views.remove(pos);
+            for &drawable in view.decorations.titlebar_drawables.iter() {
+                if let Some(id) = drawable {
+                    compositor::forget_drawable(&mut server.drawables, id);
+                }
+            }
+            awesome::unmanage_client(view.id);
         }
     }
 }
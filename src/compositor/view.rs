@@ -1,15 +1,89 @@
-use compositor::Shell;
-use wlroots::Origin;
+use compositor::{self, Decorations, Server, Shell, TitlebarSide};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use wlroots::{Area, HandleResult, Origin, Size, SurfaceHandle};
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+static NEXT_VIEW_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct View {
     pub shell: Shell,
-    pub origin: Origin
+    /// Where the client is in the output layout, including its titlebars.
+    pub origin: Origin,
+    /// Identifies the view outside of the compositor, e.g to Lua.
+    pub id: usize,
+    pub decorations: Decorations,
+    /// How opaque the view is drawn, from 0 to 1.
+    pub opacity: f32
 }
 
 impl View {
     pub fn new(shell: Shell) -> View {
         View { shell,
-               origin: Origin::default() }
+               origin: Origin::default(),
+               id: NEXT_VIEW_ID.fetch_add(1, Ordering::SeqCst),
+               decorations: Decorations::default(),
+               opacity: 1.0 }
+    }
+
+    /// Where the surface of the view is in the output layout, which is inside
+    /// of its titlebars.
+    pub fn surface_origin(&self) -> Origin {
+        let offset = self.decorations.surface_offset();
+        Origin::new(self.origin.x + offset.x, self.origin.y + offset.y)
+    }
+
+    /// The size of the client, including its titlebars.
+    pub fn client_size(&mut self) -> HandleResult<Size> {
+        let size = self.shell.geometry()?.size;
+        Ok(self.decorations.client_size(size))
+    }
+
+    /// Get the surface of the view at the output layout coordinates.
+    ///
+    /// The coordinates returned are local to the surface found.
+    pub fn surface_at(&mut self,
+                      lx: f64,
+                      ly: f64)
+                      -> HandleResult<Option<(SurfaceHandle, f64, f64)>> {
+        let Origin { x, y } = self.surface_origin();
+        self.shell.surface_at(lx - x as f64, ly - y as f64)
     }
+
+    /// The area the view covers in the output layout, including its titlebars
+    /// and border.
+    pub fn geometry(&mut self) -> HandleResult<Area> {
+        let size = self.client_size()?;
+        let Area { origin, size } = self.decorations.frame_area(size);
+        Ok(Area::new(Origin::new(self.origin.x + origin.x, self.origin.y + origin.y), size))
+    }
+
+    /// If the output layout coordinates are on the view, its titlebars or its
+    /// border.
+    pub fn contains(&mut self, lx: f64, ly: f64) -> HandleResult<bool> {
+        let Area { origin: Origin { x, y },
+                   size: Size { width, height } } = self.geometry()?;
+        Ok(lx >= x as f64 && ly >= y as f64 && lx < (x + width) as f64 && ly < (y + height) as f64)
+    }
+
+    /// Get the titlebar of the view at the output layout coordinates.
+    ///
+    /// The coordinates returned are local to the titlebar found.
+    pub fn titlebar_at(&mut self,
+                       lx: f64,
+                       ly: f64)
+                       -> HandleResult<Option<(TitlebarSide, f64, f64)>> {
+        let Origin { x, y } = self.origin;
+        let size = self.client_size()?;
+        Ok(self.decorations.titlebar_at(size, (lx - x as f64, ly - y as f64)))
+    }
+}
+
+/// Set how opaque the view with the id is drawn, from 0 to 1.
+pub fn set_opacity(view_id: usize, opacity: f32) {
+    compositor::run_later(move |compositor| {
+        let server: &mut Server = compositor.into();
+        if let Some(view) = server.views.iter_mut().find(|view| view.id == view_id) {
+            view.opacity = opacity.max(0.0).min(1.0);
+        }
+    });
 }
@@ -1,3 +1,5 @@
+use compositor;
+
 /// A representation of an Output for use in the Awesome module.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Output {
@@ -6,15 +8,128 @@ pub struct Output {
     pub focused: bool
 }
 
+/// The state of the pointer, as last seen by the compositor.
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct Pointer {
-    pub position: (f64, f64)
+    pub position: (f64, f64),
+    /// The buttons that are currently held down.
+    pub buttons: Vec<u32>,
+    /// The name of the output the pointer is on.
+    pub output: Option<String>,
+    /// The id of the view under the pointer.
+    pub view: Option<usize>
 }
 
 impl Pointer {
     /// Set the position of the pointer.
-    pub fn set_position(&mut self, pos: (f64, f64)) {
-        // TODO: post to the server
-        self.position = pos
+    ///
+    /// The cursor is warped to that position, unless it's outside of the
+    /// output layout in which case it will be warped to the closest point in
+    /// the layout and the position is updated to that point.
+    ///
+    /// If `ignore_enter` is set then the view that the pointer lands on will
+    /// not be focused, even if the focus policy says it should be.
+    pub fn set_position(&mut self, pos: (f64, f64), ignore_enter: bool) {
+        self.position = pos;
+        compositor::warp_cursor(pos, ignore_enter)
+    }
+}
+
+/// The keyboard layout state, as last seen by the compositor.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Xkb {
+    /// The description of the layouts in the keymap
+    /// (e.g "pc+us+de:2+inet(evdev)").
+    pub symbols: String,
+    /// The index of the active layout group.
+    pub group: u32
+}
+
+/// The text of the selections, as last read by the compositor.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Selection {
+    /// The primary selection, i.e the text that was last selected.
+    pub primary: Option<String>,
+    /// The clipboard.
+    pub clipboard: Option<String>
+}
+
+/// What was on the outputs, or of a view, as last captured by the compositor.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Content {
+    pub width: i32,
+    pub height: i32,
+    /// The pixels, in the cairo ARGB32 format without any row padding.
+    pub data: Vec<u8>,
+    /// If it wasn't read since it was captured.
+    pub fresh: bool
+}
+
+/// Mask that matches bindings regardless of the modifiers that are held.
+pub const ANY_MODIFIER: u32 = 1 << 15;
+
+/// The modifiers of the active xkb keymap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModifierMap {
+    /// The names of the real modifiers, where the index is the modifier's bit.
+    pub real: Vec<String>,
+    /// The names of the virtual modifiers and the real modifiers they map to.
+    pub virtual_mods: Vec<(String, u32)>,
+    /// The modifiers that don't matter when matching bindings (i.e Caps Lock
+    /// and Num Lock).
+    pub ignored: u32
+}
+
+impl Default for ModifierMap {
+    /// The modifiers as they are mapped by the default evdev keymap.
+    fn default() -> Self {
+        let real = ["Shift", "Lock", "Control", "Mod1", "Mod2", "Mod3", "Mod4", "Mod5"];
+        let virtual_mods = [("Alt", 1 << 3),
+                            ("Meta", 1 << 3),
+                            ("NumLock", 1 << 4),
+                            ("Super", 1 << 6),
+                            ("Hyper", 1 << 6),
+                            ("LevelThree", 1 << 7)];
+        ModifierMap { real: real.iter().map(|name| name.to_string()).collect(),
+                      virtual_mods: virtual_mods.iter()
+                                                .map(|&(name, mask)| (name.to_string(), mask))
+                                                .collect(),
+                      ignored: 1 << 1 | 1 << 4 }
+    }
+}
+
+impl ModifierMap {
+    /// Get the mask of real modifiers for the modifier with the given name.
+    pub fn mask(&self, name: &str) -> Option<u32> {
+        let name = match name {
+            "Any" => return Some(ANY_MODIFIER),
+            "Caps" => "Lock",
+            "Ctrl" => "Control",
+            name => name
+        };
+        if let Some(index) = self.real.iter().position(|real| real == name) {
+            return Some(1 << index)
+        }
+        self.virtual_mods.iter()
+            .find(|&&(ref virtual_mod, _)| virtual_mod == name)
+            .map(|&(_, mask)| mask)
+    }
+
+    /// Get the names of the real modifiers in the mask.
+    pub fn names(&self, mask: u32) -> Vec<String> {
+        if mask == ANY_MODIFIER {
+            return vec!["Any".into()]
+        }
+        self.real.iter()
+            .enumerate()
+            .filter(|&(index, _)| mask & (1 << index) != 0)
+            .map(|(_, name)| name.clone())
+            .collect()
+    }
+
+    /// Determine if the modifiers of a binding match the modifiers that are
+    /// currently active, ignoring the lock modifiers.
+    pub fn matches(&self, binding: u32, active: u32) -> bool {
+        binding == ANY_MODIFIER || binding == active & !self.ignored
     }
 }